@@ -1,52 +1,300 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+mod elf;
+mod macho;
+mod pe;
+
+#[cfg(test)]
+pub(crate) use pe::fixture as pe_fixture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
 pub enum BinaryArch {
     X86,
     X86_64,
+    AArch64,
+    Arm32,
+    /// ARM64 code that can be mixed with x86_64 code in one process
+    Arm64Ec,
+    /// A hybrid Windows image with both ARM64 and ARM64EC code
+    Arm64X,
+    /// arm64_32, the ILP32 ABI of watchOS
+    Arm64_32,
+    PowerPc,
+    PowerPc64,
     Unknown,
 }
 
+impl BinaryArch {
+    /// The architecture of the running process, the only one it can load plugins for
+    pub fn host() -> BinaryArch {
+        if cfg!(target_arch = "x86") {
+            BinaryArch::X86
+        } else if cfg!(target_arch = "x86_64") {
+            BinaryArch::X86_64
+        } else if cfg!(target_arch = "aarch64") {
+            BinaryArch::AArch64
+        } else if cfg!(target_arch = "arm") {
+            BinaryArch::Arm32
+        } else if cfg!(target_arch = "arm64ec") {
+            BinaryArch::Arm64Ec
+        } else if cfg!(target_arch = "powerpc") {
+            BinaryArch::PowerPc
+        } else if cfg!(target_arch = "powerpc64") {
+            BinaryArch::PowerPc64
+        } else {
+            BinaryArch::Unknown
+        }
+    }
+
+    /// Whether a process of the `host` architecture can load a binary of this one. Windows on
+    /// ARM mixes ARM64EC and x86_64 code in one process, which an x86_64 `host` only can when it
+    /// is this process and runs emulated on an ARM64 machine.
+    pub fn loadable_by(self, host: BinaryArch) -> bool {
+        let emulated_x86_64 = host == BinaryArch::X86_64 && x86_64_emulated_on_arm64();
+
+        match self {
+            BinaryArch::Arm64X => {
+                matches!(host, BinaryArch::AArch64 | BinaryArch::Arm64Ec) || emulated_x86_64
+            }
+            BinaryArch::Arm64Ec => host == BinaryArch::Arm64Ec || emulated_x86_64,
+            BinaryArch::X86_64 => matches!(host, BinaryArch::X86_64 | BinaryArch::Arm64Ec),
+            arch => arch == host,
+        }
+    }
+}
+
+impl fmt::Display for BinaryArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BinaryArch::X86 => "x86",
+            BinaryArch::X86_64 => "x86_64",
+            BinaryArch::AArch64 => "arm64",
+            BinaryArch::Arm32 => "arm",
+            BinaryArch::Arm64Ec => "arm64ec",
+            BinaryArch::Arm64X => "arm64x",
+            BinaryArch::Arm64_32 => "arm64_32",
+            BinaryArch::PowerPc => "ppc",
+            BinaryArch::PowerPc64 => "ppc64",
+            BinaryArch::Unknown => "unknown",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum BinaryFormat {
+    Pe,
+    Elf,
+    MachO,
+    /// A fat Mach-O file with one slice per architecture
+    MachOUniversal,
+}
+
+/// `EI_OSABI` of an ELF header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ElfOsAbi {
+    SystemV, // 0
+    HpUx,    // 1
+    NetBsd,  // 2
+    Linux,   // 3
+    Solaris, // 6
+    FreeBsd, // 9
+    OpenBsd, // 12
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct BinarySlice {
+    pub arch: BinaryArch,
+    /// 32 or 64
+    pub bits: u8,
+    /// Only set for ELF files
+    pub os_abi: Option<ElfOsAbi>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct BinaryInfo {
+    pub format: BinaryFormat,
+    /// Every architecture the file contains code for. Only universal binaries have several.
+    pub slices: Vec<BinarySlice>,
+}
+
+impl BinaryInfo {
+    pub fn contains(&self, arch: BinaryArch) -> bool {
+        self.slices.iter().any(|slice| slice.arch == arch)
+    }
+
+    pub fn loadable_by(&self, host: BinaryArch) -> bool {
+        self.slices.iter().any(|slice| slice.arch.loadable_by(host))
+    }
+
+    /// The slice the host would load, otherwise the first one
+    pub fn preferred_arch(&self) -> BinaryArch {
+        let host = BinaryArch::host();
+
+        self.slices
+            .iter()
+            .find(|slice| slice.arch == host)
+            .or_else(|| {
+                self.slices
+                    .iter()
+                    .find(|slice| slice.arch.loadable_by(host))
+            })
+            .or(self.slices.first())
+            .map_or(BinaryArch::Unknown, |slice| slice.arch)
+    }
+}
+
+/// Strings and versions from the `VS_VERSIONINFO` resource of a Windows DLL
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct FileVersionInfo {
+    pub file_version: Option<String>,
+    pub product_version: Option<String>,
+    pub company_name: Option<String>,
+    pub product_name: Option<String>,
+    pub file_description: Option<String>,
+    pub legal_copyright: Option<String>,
+    /// Numeric versions from `VS_FIXEDFILEINFO`, most significant part first
+    pub fixed_file_version: Option<[u16; 4]>,
+    pub fixed_product_version: Option<[u16; 4]>,
+}
+
+impl FileVersionInfo {
+    /// The product version, falling back to the file version and then to the numeric ones
+    pub fn version(&self) -> Option<String> {
+        self.product_version
+            .clone()
+            .or_else(|| self.file_version.clone())
+            .or_else(|| {
+                self.fixed_product_version
+                    .or(self.fixed_file_version)
+                    .map(|[major, minor, patch, build]| format!("{major}.{minor}.{patch}.{build}"))
+            })
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ArchDetectError {
-    #[error("File too small to be a valid PE executable")]
+    #[error("File too small to be a valid executable")]
     FileTooSmall,
     #[error("Invalid MZ header")]
     InvalidMZHeader,
     #[error("Invalid PE signature")]
     InvalidPESignature,
+    #[error("Invalid ELF header")]
+    InvalidElfHeader,
+    #[error("Invalid Mach-O header")]
+    InvalidMachOHeader,
+    #[error("Invalid PE export table")]
+    InvalidExportTable,
+    #[error("Invalid PE import table")]
+    InvalidImportTable,
+    #[error("Invalid PE resources")]
+    InvalidResources,
+    #[error("Not a PE, ELF or Mach-O file")]
+    UnknownFormat,
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
-pub fn detect_binary_arch(path: &Path) -> Result<BinaryArch, ArchDetectError> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
+pub fn detect_binary(path: &Path) -> Result<BinaryInfo, ArchDetectError> {
+    let mut reader = BufReader::new(File::open(path)?);
 
-    let mut buffer = [0u8; 64];
-    reader.read_exact(&mut buffer)?;
+    let mut magic = [0u8; 4];
+    read_at(&mut reader, 0, &mut magic)?;
 
-    if &buffer[0..2] != b"MZ" {
-        return Err(ArchDetectError::InvalidMZHeader);
+    match magic {
+        [b'M', b'Z', ..] => pe::parse(&mut reader),
+        elf::MAGIC => elf::parse(&mut reader),
+        magic if macho::is_macho(magic) => macho::parse(&mut reader),
+        _ => Err(ArchDetectError::UnknownFormat),
     }
+}
 
-    let pe_offset = u32::from_le_bytes(buffer[0x3C..0x40].try_into().unwrap());
+/// The architecture a loader would pick from the binary, see `BinaryInfo::preferred_arch`
+pub fn detect_binary_arch(path: &Path) -> Result<BinaryArch, ArchDetectError> {
+    Ok(detect_binary(path)?.preferred_arch())
+}
 
-    reader.seek(SeekFrom::Start(pe_offset as u64))?;
-    reader.read_exact(&mut buffer[0..8])?;
+/// Names exported by a Windows DLL, read from its export directory without loading it
+pub fn read_pe_exports(path: &Path) -> Result<Vec<String>, ArchDetectError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    pe::exports(&mut reader)
+}
 
-    if &buffer[0..4] != b"PE\0\0" {
-        return Err(ArchDetectError::InvalidPESignature);
-    }
+/// Names of the DLLs a Windows DLL needs to be loaded, read from its import directory
+pub fn read_pe_imports(path: &Path) -> Result<Vec<String>, ArchDetectError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    pe::imports(&mut reader)
+}
+
+/// The version resource of a Windows DLL, `None` when it has none
+pub fn read_pe_version_info(path: &Path) -> Result<Option<FileVersionInfo>, ArchDetectError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    pe::version_info(&mut reader)
+}
+
+#[cfg(windows)]
+fn x86_64_emulated_on_arm64() -> bool {
+    use std::sync::OnceLock;
+    use windows_sys::Win32::{
+        Foundation::{BOOL, HANDLE},
+        System::{
+            LibraryLoader::{GetModuleHandleW, GetProcAddress},
+            Threading::GetCurrentProcess,
+        },
+    };
+
+    // Looked up at runtime, Windows 10 before 1709 doesn't have it
+    type IsWow64Process2 = unsafe extern "system" fn(HANDLE, *mut u16, *mut u16) -> BOOL;
+    const IMAGE_FILE_MACHINE_ARM64: u16 = 0xAA64;
+
+    static EMULATED: OnceLock<bool> = OnceLock::new();
 
-    let machine = u16::from_le_bytes(buffer[4..6].try_into().unwrap());
+    *EMULATED.get_or_init(|| unsafe {
+        let kernel32: Vec<u16> = "kernel32.dll".encode_utf16().chain(Some(0)).collect();
+        let module = GetModuleHandleW(kernel32.as_ptr());
+        if module.is_null() {
+            return false;
+        }
+
+        let Some(function) = GetProcAddress(module, c"IsWow64Process2".as_ptr().cast()) else {
+            return false;
+        };
+        let is_wow64_process2: IsWow64Process2 = std::mem::transmute(function);
+
+        let mut process_machine = 0;
+        let mut native_machine = 0;
+        is_wow64_process2(
+            GetCurrentProcess(),
+            &mut process_machine,
+            &mut native_machine,
+        ) != 0
+            && native_machine == IMAGE_FILE_MACHINE_ARM64
+    })
+}
+
+// ARM64EC only exists on Windows
+#[cfg(not(windows))]
+fn x86_64_emulated_on_arm64() -> bool {
+    false
+}
 
-    Ok(match machine {
-        0x014c => BinaryArch::X86,
-        0x8664 => BinaryArch::X86_64,
-        _ => BinaryArch::Unknown,
+fn read_at(
+    reader: &mut (impl Read + Seek),
+    offset: u64,
+    buffer: &mut [u8],
+) -> Result<(), ArchDetectError> {
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(buffer).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => ArchDetectError::FileTooSmall,
+        _ => err.into(),
     })
 }
@@ -0,0 +1,199 @@
+use std::io::{Read, Seek};
+
+use super::{
+    ArchDetectError, BinaryArch, BinaryFormat, BinaryInfo, BinarySlice, ElfOsAbi, read_at,
+};
+
+pub(super) const MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+
+// EI_CLASS
+const CLASS_32: u8 = 1;
+const CLASS_64: u8 = 2;
+
+// EI_DATA
+const DATA_LITTLE_ENDIAN: u8 = 1;
+const DATA_BIG_ENDIAN: u8 = 2;
+
+// e_machine
+const MACHINE_386: u16 = 3;
+const MACHINE_PPC: u16 = 20;
+const MACHINE_PPC64: u16 = 21;
+const MACHINE_ARM: u16 = 40;
+const MACHINE_X86_64: u16 = 62;
+const MACHINE_AARCH64: u16 = 183;
+
+pub(super) fn parse(reader: &mut (impl Read + Seek)) -> Result<BinaryInfo, ArchDetectError> {
+    // e_ident, e_type and e_machine
+    let mut header = [0u8; 20];
+    read_at(reader, 0, &mut header)?;
+
+    if header[0..4] != MAGIC {
+        return Err(ArchDetectError::InvalidElfHeader);
+    }
+
+    let bits = match header[4] {
+        CLASS_32 => 32,
+        CLASS_64 => 64,
+        _ => return Err(ArchDetectError::InvalidElfHeader),
+    };
+
+    let machine = [header[18], header[19]];
+    let machine = match header[5] {
+        DATA_LITTLE_ENDIAN => u16::from_le_bytes(machine),
+        DATA_BIG_ENDIAN => u16::from_be_bytes(machine),
+        _ => return Err(ArchDetectError::InvalidElfHeader),
+    };
+
+    let arch = match machine {
+        MACHINE_386 => BinaryArch::X86,
+        MACHINE_X86_64 => BinaryArch::X86_64,
+        MACHINE_AARCH64 => BinaryArch::AArch64,
+        MACHINE_ARM => BinaryArch::Arm32,
+        MACHINE_PPC => BinaryArch::PowerPc,
+        MACHINE_PPC64 => BinaryArch::PowerPc64,
+        _ => BinaryArch::Unknown,
+    };
+
+    let os_abi = match header[7] {
+        0 => ElfOsAbi::SystemV,
+        1 => ElfOsAbi::HpUx,
+        2 => ElfOsAbi::NetBsd,
+        3 => ElfOsAbi::Linux,
+        6 => ElfOsAbi::Solaris,
+        9 => ElfOsAbi::FreeBsd,
+        12 => ElfOsAbi::OpenBsd,
+        other => ElfOsAbi::Other(other),
+    };
+
+    Ok(BinaryInfo {
+        format: BinaryFormat::Elf,
+        slices: vec![BinarySlice {
+            arch,
+            bits,
+            os_abi: Some(os_abi),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn header(class: u8, data: u8, machine: u16, os_abi: u8) -> Vec<u8> {
+        let mut bytes = vec![0u8; 64];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = class;
+        bytes[5] = data;
+        bytes[6] = 1;
+        bytes[7] = os_abi;
+
+        let machine = match data {
+            DATA_BIG_ENDIAN => machine.to_be_bytes(),
+            _ => machine.to_le_bytes(),
+        };
+        bytes[18..20].copy_from_slice(&machine);
+
+        bytes
+    }
+
+    fn slice(bytes: Vec<u8>) -> BinarySlice {
+        let info = parse(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(info.format, BinaryFormat::Elf);
+        assert_eq!(info.slices.len(), 1);
+
+        info.slices[0].clone()
+    }
+
+    #[test]
+    fn elf64_little_endian() {
+        let bytes = header(CLASS_64, DATA_LITTLE_ENDIAN, MACHINE_X86_64, 3);
+
+        assert_eq!(
+            slice(bytes),
+            BinarySlice {
+                arch: BinaryArch::X86_64,
+                bits: 64,
+                os_abi: Some(ElfOsAbi::Linux),
+            }
+        );
+    }
+
+    #[test]
+    fn elf32_little_endian() {
+        let bytes = header(CLASS_32, DATA_LITTLE_ENDIAN, MACHINE_386, 0);
+
+        assert_eq!(
+            slice(bytes),
+            BinarySlice {
+                arch: BinaryArch::X86,
+                bits: 32,
+                os_abi: Some(ElfOsAbi::SystemV),
+            }
+        );
+    }
+
+    #[test]
+    fn elf64_big_endian() {
+        let bytes = header(CLASS_64, DATA_BIG_ENDIAN, MACHINE_PPC64, 9);
+
+        assert_eq!(
+            slice(bytes),
+            BinarySlice {
+                arch: BinaryArch::PowerPc64,
+                bits: 64,
+                os_abi: Some(ElfOsAbi::FreeBsd),
+            }
+        );
+    }
+
+    #[test]
+    fn elf32_big_endian() {
+        let bytes = header(CLASS_32, DATA_BIG_ENDIAN, MACHINE_PPC, 42);
+
+        assert_eq!(
+            slice(bytes),
+            BinarySlice {
+                arch: BinaryArch::PowerPc,
+                bits: 32,
+                os_abi: Some(ElfOsAbi::Other(42)),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_machine() {
+        let bytes = header(CLASS_64, DATA_LITTLE_ENDIAN, 0x1234, 0);
+        assert_eq!(slice(bytes).arch, BinaryArch::Unknown);
+    }
+
+    #[test]
+    fn invalid_class_and_data() {
+        let mut bytes = header(CLASS_64, DATA_LITTLE_ENDIAN, MACHINE_X86_64, 0);
+        bytes[4] = 3;
+        assert!(matches!(
+            parse(&mut Cursor::new(bytes)),
+            Err(ArchDetectError::InvalidElfHeader)
+        ));
+
+        let mut bytes = header(CLASS_64, DATA_LITTLE_ENDIAN, MACHINE_X86_64, 0);
+        bytes[5] = 0;
+        assert!(matches!(
+            parse(&mut Cursor::new(bytes)),
+            Err(ArchDetectError::InvalidElfHeader)
+        ));
+    }
+
+    #[test]
+    fn truncated_header() {
+        let bytes = header(CLASS_64, DATA_LITTLE_ENDIAN, MACHINE_X86_64, 0);
+
+        for len in 0..20 {
+            assert!(matches!(
+                parse(&mut Cursor::new(&bytes[..len])),
+                Err(ArchDetectError::FileTooSmall)
+            ));
+        }
+    }
+}
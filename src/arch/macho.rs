@@ -0,0 +1,248 @@
+use std::io::{Read, Seek};
+
+use super::{ArchDetectError, BinaryArch, BinaryFormat, BinaryInfo, BinarySlice, read_at};
+
+const MH_MAGIC: u32 = 0xFEED_FACE;
+const MH_MAGIC_64: u32 = 0xFEED_FACF;
+// Fat headers are always big endian
+const FAT_MAGIC: u32 = 0xCAFE_BABE;
+const FAT_MAGIC_64: u32 = 0xCAFE_BABF;
+
+const CPU_ARCH_ABI64: i32 = 0x0100_0000;
+const CPU_TYPE_X86: i32 = 7;
+const CPU_TYPE_X86_64: i32 = CPU_TYPE_X86 | CPU_ARCH_ABI64;
+const CPU_TYPE_ARM: i32 = 12;
+const CPU_TYPE_ARM64: i32 = CPU_TYPE_ARM | CPU_ARCH_ABI64;
+// arm64_32, 64-bit instructions with 32-bit pointers, not loadable by arm64 processes
+const CPU_TYPE_ARM64_32: i32 = CPU_TYPE_ARM | 0x0200_0000;
+const CPU_TYPE_POWERPC: i32 = 18;
+const CPU_TYPE_POWERPC64: i32 = CPU_TYPE_POWERPC | CPU_ARCH_ABI64;
+
+// Java class files share the fat magic, their version makes the slice count much larger
+const MAX_FAT_SLICES: u32 = 30;
+
+pub(super) fn is_macho(magic: [u8; 4]) -> bool {
+    let thin = [MH_MAGIC, MH_MAGIC_64];
+
+    thin.contains(&u32::from_le_bytes(magic))
+        || thin.contains(&u32::from_be_bytes(magic))
+        || [FAT_MAGIC, FAT_MAGIC_64].contains(&u32::from_be_bytes(magic))
+}
+
+pub(super) fn parse(reader: &mut (impl Read + Seek)) -> Result<BinaryInfo, ArchDetectError> {
+    // Magic and cputype, or magic and slice count of a fat header
+    let mut header = [0u8; 8];
+    read_at(reader, 0, &mut header)?;
+
+    let magic = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let count = u32::from_be_bytes(header[4..8].try_into().unwrap());
+
+    match magic {
+        FAT_MAGIC => parse_fat(reader, count, 20),
+        FAT_MAGIC_64 => parse_fat(reader, count, 32),
+        _ => Ok(BinaryInfo {
+            format: BinaryFormat::MachO,
+            slices: vec![thin_slice(header)?],
+        }),
+    }
+}
+
+fn thin_slice(header: [u8; 8]) -> Result<BinarySlice, ArchDetectError> {
+    let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let cpu_type = header[4..8].try_into().unwrap();
+
+    // Thin headers are written in the byte order of their architecture
+    let (magic, cpu_type) = if [MH_MAGIC, MH_MAGIC_64].contains(&magic) {
+        (magic, i32::from_le_bytes(cpu_type))
+    } else {
+        (magic.swap_bytes(), i32::from_be_bytes(cpu_type))
+    };
+
+    let bits = match magic {
+        MH_MAGIC => 32,
+        MH_MAGIC_64 => 64,
+        _ => return Err(ArchDetectError::InvalidMachOHeader),
+    };
+
+    Ok(BinarySlice {
+        arch: arch(cpu_type),
+        bits,
+        os_abi: None,
+    })
+}
+
+fn parse_fat(
+    reader: &mut (impl Read + Seek),
+    count: u32,
+    entry_size: u64,
+) -> Result<BinaryInfo, ArchDetectError> {
+    if count == 0 || count > MAX_FAT_SLICES {
+        return Err(ArchDetectError::InvalidMachOHeader);
+    }
+
+    let slices = (0..count as u64)
+        .map(|index| {
+            // `fat_arch` and `fat_arch_64` both start with cputype
+            let mut cpu_type = [0u8; 4];
+            read_at(reader, 8 + index * entry_size, &mut cpu_type)?;
+            let cpu_type = i32::from_be_bytes(cpu_type);
+
+            Ok(BinarySlice {
+                arch: arch(cpu_type),
+                bits: if cpu_type & CPU_ARCH_ABI64 != 0 {
+                    64
+                } else {
+                    32
+                },
+                os_abi: None,
+            })
+        })
+        .collect::<Result<_, ArchDetectError>>()?;
+
+    Ok(BinaryInfo {
+        format: BinaryFormat::MachOUniversal,
+        slices,
+    })
+}
+
+fn arch(cpu_type: i32) -> BinaryArch {
+    match cpu_type {
+        CPU_TYPE_X86 => BinaryArch::X86,
+        CPU_TYPE_X86_64 => BinaryArch::X86_64,
+        CPU_TYPE_ARM64 => BinaryArch::AArch64,
+        CPU_TYPE_ARM64_32 => BinaryArch::Arm64_32,
+        CPU_TYPE_ARM => BinaryArch::Arm32,
+        CPU_TYPE_POWERPC => BinaryArch::PowerPc,
+        CPU_TYPE_POWERPC64 => BinaryArch::PowerPc64,
+        _ => BinaryArch::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn thin(magic: [u8; 4], cpu_type: [u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0u8; 32];
+        bytes[0..4].copy_from_slice(&magic);
+        bytes[4..8].copy_from_slice(&cpu_type);
+        bytes
+    }
+
+    fn fat(magic: u32, entry_size: usize, cpu_types: &[i32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(magic.to_be_bytes());
+        bytes.extend((cpu_types.len() as u32).to_be_bytes());
+
+        for cpu_type in cpu_types {
+            let mut entry = vec![0u8; entry_size];
+            entry[0..4].copy_from_slice(&cpu_type.to_be_bytes());
+            bytes.extend(entry);
+        }
+
+        bytes
+    }
+
+    fn parse_bytes(bytes: &[u8]) -> Result<BinaryInfo, ArchDetectError> {
+        parse(&mut Cursor::new(bytes))
+    }
+
+    fn archs(info: &BinaryInfo) -> Vec<(BinaryArch, u8)> {
+        info.slices
+            .iter()
+            .map(|slice| (slice.arch, slice.bits))
+            .collect()
+    }
+
+    #[test]
+    fn thin_little_endian() {
+        let bytes = thin(MH_MAGIC_64.to_le_bytes(), CPU_TYPE_X86_64.to_le_bytes());
+        assert!(is_macho(bytes[0..4].try_into().unwrap()));
+
+        let info = parse_bytes(&bytes).unwrap();
+        assert_eq!(info.format, BinaryFormat::MachO);
+        assert_eq!(archs(&info), [(BinaryArch::X86_64, 64)]);
+    }
+
+    #[test]
+    fn thin_big_endian() {
+        let bytes = thin(MH_MAGIC.to_be_bytes(), CPU_TYPE_POWERPC.to_be_bytes());
+        assert!(is_macho(bytes[0..4].try_into().unwrap()));
+
+        let info = parse_bytes(&bytes).unwrap();
+        assert_eq!(info.format, BinaryFormat::MachO);
+        assert_eq!(archs(&info), [(BinaryArch::PowerPc, 32)]);
+    }
+
+    #[test]
+    fn fat32_universal() {
+        let bytes = fat(FAT_MAGIC, 20, &[CPU_TYPE_X86_64, CPU_TYPE_ARM64]);
+
+        let info = parse_bytes(&bytes).unwrap();
+        assert_eq!(info.format, BinaryFormat::MachOUniversal);
+        assert_eq!(
+            archs(&info),
+            [(BinaryArch::X86_64, 64), (BinaryArch::AArch64, 64)]
+        );
+    }
+
+    #[test]
+    fn fat64_universal() {
+        let bytes = fat(
+            FAT_MAGIC_64,
+            32,
+            &[CPU_TYPE_X86, CPU_TYPE_POWERPC, CPU_TYPE_POWERPC64],
+        );
+
+        let info = parse_bytes(&bytes).unwrap();
+        assert_eq!(info.format, BinaryFormat::MachOUniversal);
+        assert_eq!(
+            archs(&info),
+            [
+                (BinaryArch::X86, 32),
+                (BinaryArch::PowerPc, 32),
+                (BinaryArch::PowerPc64, 64)
+            ]
+        );
+    }
+
+    #[test]
+    fn arm64_32_is_not_arm64() {
+        let bytes = fat(FAT_MAGIC, 20, &[CPU_TYPE_ARM64_32]);
+
+        let info = parse_bytes(&bytes).unwrap();
+        assert_eq!(archs(&info), [(BinaryArch::Arm64_32, 32)]);
+        assert!(!info.loadable_by(BinaryArch::AArch64));
+    }
+
+    #[test]
+    fn java_class_file_is_rejected() {
+        // Magic, minor version 0, major version 52
+        let bytes = [0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x34];
+
+        assert!(matches!(
+            parse_bytes(&bytes),
+            Err(ArchDetectError::InvalidMachOHeader)
+        ));
+    }
+
+    #[test]
+    fn truncated_input() {
+        let bytes = thin(MH_MAGIC_64.to_le_bytes(), CPU_TYPE_X86_64.to_le_bytes());
+        for len in 0..8 {
+            assert!(matches!(
+                parse_bytes(&bytes[..len]),
+                Err(ArchDetectError::FileTooSmall)
+            ));
+        }
+
+        // The header promises two slices but the second entry is cut off
+        let bytes = fat(FAT_MAGIC, 20, &[CPU_TYPE_X86_64, CPU_TYPE_ARM64]);
+        assert!(matches!(
+            parse_bytes(&bytes[..8 + 20 + 2]),
+            Err(ArchDetectError::FileTooSmall)
+        ));
+    }
+}
@@ -0,0 +1,664 @@
+use std::io::{Read, Seek, SeekFrom};
+
+mod version;
+
+pub(super) use version::version_info;
+
+use super::{ArchDetectError, BinaryArch, BinaryFormat, BinaryInfo, BinarySlice, read_at};
+
+// IMAGE_FILE_MACHINE_*
+const MACHINE_I386: u16 = 0x014c;
+const MACHINE_AMD64: u16 = 0x8664;
+const MACHINE_ARM: u16 = 0x01c0;
+const MACHINE_THUMB: u16 = 0x01c2;
+const MACHINE_ARMNT: u16 = 0x01c4;
+const MACHINE_ARM64: u16 = 0xaa64;
+const MACHINE_ARM64EC: u16 = 0xa641;
+
+// IMAGE_NT_OPTIONAL_HDR64_MAGIC, everything else is a 32-bit image
+const OPTIONAL_HEADER_PE32_PLUS: u16 = 0x20b;
+
+// Offset of the data directories in the optional header
+const DATA_DIRECTORIES_PE32: u64 = 96;
+const DATA_DIRECTORIES_PE32_PLUS: u64 = 112;
+// IMAGE_DIRECTORY_ENTRY_*
+const DIRECTORY_EXPORT: u32 = 0;
+const DIRECTORY_IMPORT: u32 = 1;
+const DIRECTORY_LOAD_CONFIG: u32 = 10;
+// `CHPEMetadataPointer` in IMAGE_LOAD_CONFIG_DIRECTORY64
+const CHPE_METADATA_OFFSET: usize = 200;
+
+// Limits that keep a corrupt export or import directory from making us read the whole file
+const MAX_EXPORTS: u32 = 65536;
+const MAX_IMPORTED_DLLS: u64 = 4096;
+const MAX_NAME_LEN: u64 = 1024;
+
+pub(super) fn parse(reader: &mut (impl Read + Seek)) -> Result<BinaryInfo, ArchDetectError> {
+    let (pe_offset, headers) = read_headers(reader)?;
+
+    let machine = u16::from_le_bytes(headers[4..6].try_into().unwrap());
+    let optional_magic = u16::from_le_bytes(headers[24..26].try_into().unwrap());
+
+    let arch = match machine {
+        MACHINE_I386 => BinaryArch::X86,
+        MACHINE_AMD64 | MACHINE_ARM64 if optional_magic == OPTIONAL_HEADER_PE32_PLUS => {
+            let image = Image::read(reader, pe_offset, &headers)?;
+
+            // Hybrid images describe their native code in the CHPE metadata
+            match (machine, image.has_chpe_metadata(reader)?) {
+                (MACHINE_AMD64, true) => BinaryArch::Arm64Ec,
+                (MACHINE_AMD64, false) => BinaryArch::X86_64,
+                (_, true) => BinaryArch::Arm64X,
+                (_, false) => BinaryArch::AArch64,
+            }
+        }
+        MACHINE_AMD64 => BinaryArch::X86_64,
+        MACHINE_ARM64 => BinaryArch::AArch64,
+        MACHINE_ARM64EC => BinaryArch::Arm64Ec,
+        MACHINE_ARM | MACHINE_THUMB | MACHINE_ARMNT => BinaryArch::Arm32,
+        _ => BinaryArch::Unknown,
+    };
+
+    Ok(BinaryInfo {
+        format: BinaryFormat::Pe,
+        slices: vec![BinarySlice {
+            arch,
+            bits: if optional_magic == OPTIONAL_HEADER_PE32_PLUS {
+                64
+            } else {
+                32
+            },
+            os_abi: None,
+        }],
+    })
+}
+
+/// Names of the symbols the image exports. Symbols exported only by ordinal have no name and are
+/// left out.
+pub(super) fn exports(reader: &mut (impl Read + Seek)) -> Result<Vec<String>, ArchDetectError> {
+    let (pe_offset, headers) = read_headers(reader)?;
+    let image = Image::read(reader, pe_offset, &headers)?;
+
+    let Some((rva, _)) = image.directory(reader, DIRECTORY_EXPORT)? else {
+        return Ok(Vec::new());
+    };
+    let offset = image
+        .file_offset(rva)
+        .ok_or(ArchDetectError::InvalidExportTable)?;
+
+    // IMAGE_EXPORT_DIRECTORY
+    let mut directory = [0u8; 40];
+    read_at(reader, offset, &mut directory)?;
+
+    let num_names = u32::from_le_bytes(directory[24..28].try_into().unwrap());
+    let names_rva = u32::from_le_bytes(directory[32..36].try_into().unwrap());
+
+    if num_names == 0 {
+        return Ok(Vec::new());
+    }
+    if num_names > MAX_EXPORTS {
+        return Err(ArchDetectError::InvalidExportTable);
+    }
+
+    let names_offset = image
+        .file_offset(names_rva)
+        .ok_or(ArchDetectError::InvalidExportTable)?;
+    let mut name_rvas = vec![0u8; num_names as usize * 4];
+    read_at(reader, names_offset, &mut name_rvas)?;
+
+    name_rvas
+        .chunks_exact(4)
+        .map(|name_rva| {
+            let name_rva = u32::from_le_bytes(name_rva.try_into().unwrap());
+            let offset = image
+                .file_offset(name_rva)
+                .ok_or(ArchDetectError::InvalidExportTable)?;
+
+            read_c_string(reader, offset, ArchDetectError::InvalidExportTable)
+        })
+        .collect()
+}
+
+/// Names of the DLLs the image imports from, in the order of its import directory. Delay-loaded
+/// DLLs aren't included, a missing one doesn't keep the image from loading.
+pub(super) fn imports(reader: &mut (impl Read + Seek)) -> Result<Vec<String>, ArchDetectError> {
+    let (pe_offset, headers) = read_headers(reader)?;
+    let image = Image::read(reader, pe_offset, &headers)?;
+
+    let Some((rva, _)) = image.directory(reader, DIRECTORY_IMPORT)? else {
+        return Ok(Vec::new());
+    };
+    let offset = image
+        .file_offset(rva)
+        .ok_or(ArchDetectError::InvalidImportTable)?;
+
+    let mut dlls = Vec::new();
+
+    // IMAGE_IMPORT_DESCRIPTORs, up to an all-zero one
+    for index in 0..MAX_IMPORTED_DLLS {
+        let mut descriptor = [0u8; 20];
+        read_at(reader, offset + index * 20, &mut descriptor)?;
+
+        if descriptor == [0u8; 20] {
+            return Ok(dlls);
+        }
+
+        let name_rva = u32::from_le_bytes(descriptor[12..16].try_into().unwrap());
+        let name_offset = image
+            .file_offset(name_rva)
+            .ok_or(ArchDetectError::InvalidImportTable)?;
+
+        dlls.push(read_c_string(
+            reader,
+            name_offset,
+            ArchDetectError::InvalidImportTable,
+        )?);
+    }
+
+    Err(ArchDetectError::InvalidImportTable)
+}
+
+fn read_headers(reader: &mut (impl Read + Seek)) -> Result<(u64, [u8; 26]), ArchDetectError> {
+    let mut dos_header = [0u8; 64];
+    read_at(reader, 0, &mut dos_header)?;
+
+    if &dos_header[0..2] != b"MZ" {
+        return Err(ArchDetectError::InvalidMZHeader);
+    }
+
+    let pe_offset = u32::from_le_bytes(dos_header[0x3C..0x40].try_into().unwrap()) as u64;
+
+    // Signature, COFF file header and the magic of the optional header
+    let mut headers = [0u8; 26];
+    read_at(reader, pe_offset, &mut headers)?;
+
+    if &headers[0..4] != b"PE\0\0" {
+        return Err(ArchDetectError::InvalidPESignature);
+    }
+
+    Ok((pe_offset, headers))
+}
+
+fn read_c_string(
+    reader: &mut (impl Read + Seek),
+    offset: u64,
+    unterminated: ArchDetectError,
+) -> Result<String, ArchDetectError> {
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let mut bytes = Vec::new();
+    reader.by_ref().take(MAX_NAME_LEN).read_to_end(&mut bytes)?;
+
+    let len = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or(unterminated)?;
+
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+}
+
+/// The parts of a PE32 or PE32+ image needed to follow RVAs
+struct Image {
+    data_directories: u64,
+    num_directories: u32,
+    sections: Vec<Section>,
+}
+
+impl Image {
+    fn read(
+        reader: &mut (impl Read + Seek),
+        pe_offset: u64,
+        headers: &[u8; 26],
+    ) -> Result<Image, ArchDetectError> {
+        let num_sections = u16::from_le_bytes(headers[6..8].try_into().unwrap());
+        let optional_size = u16::from_le_bytes(headers[20..22].try_into().unwrap());
+        let optional_magic = u16::from_le_bytes(headers[24..26].try_into().unwrap());
+
+        let optional_header = pe_offset + 24;
+        let section_table = optional_header + optional_size as u64;
+
+        let data_directories = optional_header
+            + if optional_magic == OPTIONAL_HEADER_PE32_PLUS {
+                DATA_DIRECTORIES_PE32_PLUS
+            } else {
+                DATA_DIRECTORIES_PE32
+            };
+
+        // `NumberOfRvaAndSizes` precedes the directories
+        let mut num_directories = [0u8; 4];
+        read_at(reader, data_directories - 4, &mut num_directories)?;
+        let num_directories = u32::from_le_bytes(num_directories);
+
+        let sections = (0..num_sections as u64)
+            .map(|index| {
+                let mut header = [0u8; 40];
+                read_at(reader, section_table + index * 40, &mut header)?;
+                let field = |offset: usize| {
+                    u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap())
+                };
+
+                Ok(Section {
+                    virtual_size: field(8),
+                    virtual_address: field(12),
+                    raw_size: field(16),
+                    raw_offset: field(20),
+                })
+            })
+            .collect::<Result<_, ArchDetectError>>()?;
+
+        Ok(Image {
+            data_directories,
+            num_directories,
+            sections,
+        })
+    }
+
+    /// RVA and size of a data directory, if the image has it
+    fn directory(
+        &self,
+        reader: &mut (impl Read + Seek),
+        index: u32,
+    ) -> Result<Option<(u32, u32)>, ArchDetectError> {
+        if index >= self.num_directories {
+            return Ok(None);
+        }
+
+        let mut directory = [0u8; 8];
+        read_at(
+            reader,
+            self.data_directories + index as u64 * 8,
+            &mut directory,
+        )?;
+
+        let rva = u32::from_le_bytes(directory[0..4].try_into().unwrap());
+        let size = u32::from_le_bytes(directory[4..8].try_into().unwrap());
+
+        Ok((rva != 0).then_some((rva, size)))
+    }
+
+    /// Where the byte at `rva` is stored in the file. The zero-filled tail of a section that is
+    /// larger in memory than on disk isn't stored anywhere.
+    fn file_offset(&self, rva: u32) -> Option<u64> {
+        let section = self.sections.iter().find(|section| {
+            let size = section.virtual_size.max(section.raw_size);
+            rva >= section.virtual_address && rva - section.virtual_address < size
+        })?;
+
+        let offset = rva - section.virtual_address;
+        (offset < section.raw_size).then(|| section.raw_offset as u64 + offset as u64)
+    }
+
+    fn has_chpe_metadata(&self, reader: &mut (impl Read + Seek)) -> Result<bool, ArchDetectError> {
+        let Some(offset) = self
+            .directory(reader, DIRECTORY_LOAD_CONFIG)?
+            .and_then(|(rva, _)| self.file_offset(rva))
+        else {
+            return Ok(false);
+        };
+
+        let mut load_config = [0u8; CHPE_METADATA_OFFSET + 8];
+        if read_at(reader, offset, &mut load_config).is_err() {
+            return Ok(false);
+        }
+
+        // Older load configs end before the field
+        let size = u32::from_le_bytes(load_config[0..4].try_into().unwrap()) as usize;
+        if size < load_config.len() {
+            return Ok(false);
+        }
+
+        let chpe_metadata =
+            u64::from_le_bytes(load_config[CHPE_METADATA_OFFSET..].try_into().unwrap());
+        Ok(chpe_metadata != 0)
+    }
+}
+
+/// Builds minimal images for tests
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    pub(crate) use super::version::fixture::{dll_with_version_info, expected_version_info};
+
+    /// RVA of the only section of a fixture image
+    pub(crate) const SECTION_RVA: u32 = 0x1000;
+
+    const PE_OFFSET: usize = 0x80;
+    const SECTION_OFFSET: usize = 0x400;
+
+    /// Writes `data` at `offset`, growing `bytes` as needed
+    pub(crate) fn put(bytes: &mut Vec<u8>, offset: usize, data: &[u8]) {
+        if bytes.len() < offset + data.len() {
+            bytes.resize(offset + data.len(), 0);
+        }
+        bytes[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    /// Writes an IMAGE_EXPORT_DIRECTORY for `names` at `at` in `section`, followed by the names
+    pub(crate) fn export_directory(section: &mut Vec<u8>, at: usize, names: &[&str]) {
+        let names_at = at + 40;
+        let mut string_at = names_at + names.len() * 4;
+
+        put(section, at, &[0; 40]);
+        put(section, at + 24, &(names.len() as u32).to_le_bytes());
+        put(
+            section,
+            at + 32,
+            &(SECTION_RVA + names_at as u32).to_le_bytes(),
+        );
+
+        for (index, name) in names.iter().enumerate() {
+            let name_rva = SECTION_RVA + string_at as u32;
+            put(section, names_at + index * 4, &name_rva.to_le_bytes());
+            put(section, string_at, name.as_bytes());
+            put(section, string_at + name.len(), &[0]);
+            string_at += name.len() + 1;
+        }
+    }
+
+    /// A 64-bit DLL exporting `names`
+    pub(crate) fn dll_exporting(names: &[&str]) -> Vec<u8> {
+        let mut section = Vec::new();
+        export_directory(&mut section, 0, names);
+        let size = section.len() as u32;
+
+        image(
+            MACHINE_AMD64,
+            true,
+            &section,
+            &[(DIRECTORY_EXPORT, 0, size)],
+        )
+    }
+
+    /// Writes IMAGE_IMPORT_DESCRIPTORs for `dlls` at `at` in `section`, followed by the names
+    pub(crate) fn import_directory(section: &mut Vec<u8>, at: usize, dlls: &[&str]) {
+        let mut string_at = at + (dlls.len() + 1) * 20;
+
+        put(section, at, &vec![0; (dlls.len() + 1) * 20]);
+
+        for (index, dll) in dlls.iter().enumerate() {
+            let descriptor = at + index * 20;
+            let name_rva = SECTION_RVA + string_at as u32;
+            put(section, descriptor + 12, &name_rva.to_le_bytes());
+            put(section, descriptor + 16, &name_rva.to_le_bytes());
+            put(section, string_at, dll.as_bytes());
+            put(section, string_at + dll.len(), &[0]);
+            string_at += dll.len() + 1;
+        }
+    }
+
+    /// A 64-bit DLL importing from `dlls`
+    pub(crate) fn dll_importing(dlls: &[&str]) -> Vec<u8> {
+        let mut section = Vec::new();
+        import_directory(&mut section, 0, dlls);
+        let size = (dlls.len() as u32 + 1) * 20;
+
+        image(
+            MACHINE_AMD64,
+            true,
+            &section,
+            &[(DIRECTORY_IMPORT, 0, size)],
+        )
+    }
+
+    /// An image whose only section holds `section` at [`SECTION_RVA`]. Directories are given as
+    /// their index, offset in the section and size.
+    pub(crate) fn image(
+        machine: u16,
+        pe32_plus: bool,
+        section: &[u8],
+        directories: &[(u32, usize, u32)],
+    ) -> Vec<u8> {
+        let (magic, optional_size, data_directories) = if pe32_plus {
+            (
+                OPTIONAL_HEADER_PE32_PLUS,
+                240u16,
+                DATA_DIRECTORIES_PE32_PLUS,
+            )
+        } else {
+            (0x10b, 224, DATA_DIRECTORIES_PE32)
+        };
+        let optional_header = PE_OFFSET + 24;
+        let data_directories = optional_header + data_directories as usize;
+        let section_table = optional_header + optional_size as usize;
+
+        let mut bytes = vec![0u8; SECTION_OFFSET];
+        put(&mut bytes, 0, b"MZ");
+        put(&mut bytes, 0x3C, &(PE_OFFSET as u32).to_le_bytes());
+
+        put(&mut bytes, PE_OFFSET, b"PE\0\0");
+        put(&mut bytes, PE_OFFSET + 4, &machine.to_le_bytes());
+        put(&mut bytes, PE_OFFSET + 6, &1u16.to_le_bytes());
+        put(&mut bytes, PE_OFFSET + 20, &optional_size.to_le_bytes());
+        put(&mut bytes, optional_header, &magic.to_le_bytes());
+        put(&mut bytes, data_directories - 4, &16u32.to_le_bytes());
+
+        for &(index, offset, size) in directories {
+            let directory = data_directories + index as usize * 8;
+            put(
+                &mut bytes,
+                directory,
+                &(SECTION_RVA + offset as u32).to_le_bytes(),
+            );
+            put(&mut bytes, directory + 4, &size.to_le_bytes());
+        }
+
+        let section_size = (section.len() as u32).to_le_bytes();
+        put(&mut bytes, section_table + 8, &section_size);
+        put(&mut bytes, section_table + 12, &SECTION_RVA.to_le_bytes());
+        put(&mut bytes, section_table + 16, &section_size);
+        put(
+            &mut bytes,
+            section_table + 20,
+            &(SECTION_OFFSET as u32).to_le_bytes(),
+        );
+
+        bytes.extend(section);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::fixture::*;
+    use super::*;
+
+    const LOAD_CONFIG_SIZE: u32 = 0x140;
+
+    /// A section holding only a load config with the given `CHPEMetadataPointer`
+    fn load_config(size: u32, chpe_metadata: u64) -> Vec<u8> {
+        let mut section = vec![0u8; LOAD_CONFIG_SIZE as usize];
+        put(&mut section, 0, &size.to_le_bytes());
+        put(
+            &mut section,
+            CHPE_METADATA_OFFSET,
+            &chpe_metadata.to_le_bytes(),
+        );
+        section
+    }
+
+    fn with_load_config(machine: u16, size: u32, chpe_metadata: u64) -> Vec<u8> {
+        image(
+            machine,
+            true,
+            &load_config(size, chpe_metadata),
+            &[(DIRECTORY_LOAD_CONFIG, 0, size)],
+        )
+    }
+
+    fn arch(bytes: &[u8]) -> (BinaryArch, u8) {
+        let info = parse(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(info.format, BinaryFormat::Pe);
+        assert_eq!(info.slices.len(), 1);
+        (info.slices[0].arch, info.slices[0].bits)
+    }
+
+    #[test]
+    fn plain_images() {
+        let arm64 = image(MACHINE_ARM64, true, &[0; 16], &[]);
+        assert_eq!(arch(&arm64), (BinaryArch::AArch64, 64));
+
+        let x86_64 = image(MACHINE_AMD64, true, &[0; 16], &[]);
+        assert_eq!(arch(&x86_64), (BinaryArch::X86_64, 64));
+
+        let x86 = image(MACHINE_I386, false, &[0; 16], &[]);
+        assert_eq!(arch(&x86), (BinaryArch::X86, 32));
+    }
+
+    #[test]
+    fn arm64_without_chpe_metadata() {
+        let bytes = with_load_config(MACHINE_ARM64, LOAD_CONFIG_SIZE, 0);
+        assert_eq!(arch(&bytes), (BinaryArch::AArch64, 64));
+    }
+
+    #[test]
+    fn arm64_with_chpe_metadata_is_arm64x() {
+        let bytes = with_load_config(MACHINE_ARM64, LOAD_CONFIG_SIZE, 0x1_8000_2000);
+        assert_eq!(arch(&bytes), (BinaryArch::Arm64X, 64));
+    }
+
+    #[test]
+    fn amd64_with_chpe_metadata_is_arm64ec() {
+        let bytes = with_load_config(MACHINE_AMD64, LOAD_CONFIG_SIZE, 0x1_8000_2000);
+        assert_eq!(arch(&bytes), (BinaryArch::Arm64Ec, 64));
+
+        let bytes = with_load_config(MACHINE_AMD64, LOAD_CONFIG_SIZE, 0);
+        assert_eq!(arch(&bytes), (BinaryArch::X86_64, 64));
+    }
+
+    #[test]
+    fn load_config_ending_before_chpe_metadata() {
+        // The pointer field holds garbage past the end of an older, shorter load config
+        let bytes = with_load_config(MACHINE_AMD64, 148, 0x1_8000_2000);
+        assert_eq!(arch(&bytes), (BinaryArch::X86_64, 64));
+    }
+
+    #[test]
+    fn exported_names() {
+        let bytes = dll_exporting(&["VSTPluginMain", "main", "DllMain"]);
+        assert_eq!(
+            exports(&mut Cursor::new(bytes)).unwrap(),
+            ["VSTPluginMain", "main", "DllMain"]
+        );
+
+        let bytes = dll_exporting(&[]);
+        assert!(exports(&mut Cursor::new(bytes)).unwrap().is_empty());
+
+        let bytes = image(MACHINE_AMD64, true, &[0; 16], &[]);
+        assert!(exports(&mut Cursor::new(bytes)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn export_directory_outside_sections() {
+        let bytes = image(
+            MACHINE_AMD64,
+            true,
+            &[0; 16],
+            &[(DIRECTORY_EXPORT, 0x100, 40)],
+        );
+        assert!(matches!(
+            exports(&mut Cursor::new(bytes)),
+            Err(ArchDetectError::InvalidExportTable)
+        ));
+    }
+
+    #[test]
+    fn imported_dlls_pe32_plus() {
+        let bytes = dll_importing(&["KERNEL32.dll", "VCRUNTIME140.dll", "bundled.dll"]);
+        assert_eq!(
+            imports(&mut Cursor::new(bytes)).unwrap(),
+            ["KERNEL32.dll", "VCRUNTIME140.dll", "bundled.dll"]
+        );
+    }
+
+    #[test]
+    fn imported_dlls_pe32() {
+        let mut section = vec![0u8; 0x10];
+        import_directory(&mut section, 0x10, &["USER32.dll", "msvcr120.dll"]);
+        let bytes = image(
+            MACHINE_I386,
+            false,
+            &section,
+            &[(DIRECTORY_IMPORT, 0x10, 60)],
+        );
+
+        assert_eq!(
+            imports(&mut Cursor::new(bytes)).unwrap(),
+            ["USER32.dll", "msvcr120.dll"]
+        );
+    }
+
+    #[test]
+    fn empty_import_table() {
+        let bytes = dll_importing(&[]);
+        assert!(imports(&mut Cursor::new(bytes)).unwrap().is_empty());
+
+        let bytes = image(MACHINE_AMD64, true, &[0; 16], &[]);
+        assert!(imports(&mut Cursor::new(bytes)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn import_name_outside_sections() {
+        let mut section = Vec::new();
+        import_directory(&mut section, 0, &["lost.dll"]);
+        put(&mut section, 12, &0x9000u32.to_le_bytes());
+        let bytes = image(MACHINE_AMD64, true, &section, &[(DIRECTORY_IMPORT, 0, 40)]);
+
+        assert!(matches!(
+            imports(&mut Cursor::new(bytes)),
+            Err(ArchDetectError::InvalidImportTable)
+        ));
+    }
+
+    #[test]
+    fn file_offset_ends_with_raw_data() {
+        let image = Image {
+            data_directories: 0,
+            num_directories: 0,
+            sections: vec![
+                Section {
+                    virtual_address: 0x1000,
+                    virtual_size: 0x2000,
+                    raw_offset: 0x400,
+                    raw_size: 0x200,
+                },
+                Section {
+                    virtual_address: 0x3000,
+                    virtual_size: 0x100,
+                    raw_offset: 0x600,
+                    raw_size: 0x200,
+                },
+            ],
+        };
+
+        assert_eq!(image.file_offset(0x1000), Some(0x400));
+        assert_eq!(image.file_offset(0x11ff), Some(0x5ff));
+        // Zero-filled in memory, the file has the next section's data here
+        assert_eq!(image.file_offset(0x1200), None);
+        assert_eq!(image.file_offset(0x2fff), None);
+        // Raw data past the virtual size is still mapped
+        assert_eq!(image.file_offset(0x31ff), Some(0x7ff));
+        assert_eq!(image.file_offset(0x3200), None);
+        assert_eq!(image.file_offset(0x800), None);
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = image(MACHINE_ARM64, true, &[0; 16], &[]);
+
+        assert!(parse(&mut Cursor::new(&bytes[..0x90])).is_err());
+        assert!(matches!(
+            parse(&mut Cursor::new(b"MZ")),
+            Err(ArchDetectError::FileTooSmall)
+        ));
+    }
+}
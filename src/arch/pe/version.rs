@@ -0,0 +1,473 @@
+use std::io::{Read, Seek};
+
+use super::{Image, read_headers};
+use crate::arch::{ArchDetectError, FileVersionInfo, read_at};
+
+// IMAGE_DIRECTORY_ENTRY_RESOURCE
+const DIRECTORY_RESOURCE: u32 = 2;
+const RT_VERSION: u32 = 16;
+// High bit of `OffsetToData` in IMAGE_RESOURCE_DIRECTORY_ENTRY
+const SUBDIRECTORY: u32 = 0x8000_0000;
+
+const MAX_VERSION_INFO_SIZE: u32 = 64 * 1024;
+// VS_FIXEDFILEINFO.dwSignature
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+
+/// Reads the `VS_VERSIONINFO` resource. The first name and language of the `RT_VERSION` type are
+/// used, DLLs rarely have more than one.
+pub(in crate::arch) fn version_info(
+    reader: &mut (impl Read + Seek),
+) -> Result<Option<FileVersionInfo>, ArchDetectError> {
+    let (pe_offset, headers) = read_headers(reader)?;
+    let image = Image::read(reader, pe_offset, &headers)?;
+
+    let Some((rva, _)) = image.directory(reader, DIRECTORY_RESOURCE)? else {
+        return Ok(None);
+    };
+    let root = image
+        .file_offset(rva)
+        .ok_or(ArchDetectError::InvalidResources)?;
+
+    // Type, name and language level of the resource tree
+    let mut entry = 0;
+    for (level, id) in [Some(RT_VERSION), None, None].into_iter().enumerate() {
+        if level > 0 && entry & SUBDIRECTORY == 0 {
+            return Err(ArchDetectError::InvalidResources);
+        }
+
+        match find_entry(reader, root, entry & !SUBDIRECTORY, id)? {
+            Some(found) => entry = found,
+            None => return Ok(None),
+        }
+    }
+
+    if entry & SUBDIRECTORY != 0 {
+        return Err(ArchDetectError::InvalidResources);
+    }
+
+    // IMAGE_RESOURCE_DATA_ENTRY
+    let mut data_entry = [0u8; 8];
+    read_at(reader, root + entry as u64, &mut data_entry)?;
+    let data_rva = u32::from_le_bytes(data_entry[0..4].try_into().unwrap());
+    let size = u32::from_le_bytes(data_entry[4..8].try_into().unwrap());
+
+    if size > MAX_VERSION_INFO_SIZE {
+        return Err(ArchDetectError::InvalidResources);
+    }
+
+    let offset = image
+        .file_offset(data_rva)
+        .ok_or(ArchDetectError::InvalidResources)?;
+    let mut data = vec![0u8; size as usize];
+    read_at(reader, offset, &mut data)?;
+
+    parse_version_info(&data)
+        .map(Some)
+        .ok_or(ArchDetectError::InvalidResources)
+}
+
+/// `OffsetToData` of the entry with the given ID, or of the first entry, in the resource
+/// directory at `directory`. Offsets are relative to the start of the resources.
+fn find_entry(
+    reader: &mut (impl Read + Seek),
+    root: u64,
+    directory: u32,
+    id: Option<u32>,
+) -> Result<Option<u32>, ArchDetectError> {
+    // IMAGE_RESOURCE_DIRECTORY
+    let mut header = [0u8; 16];
+    read_at(reader, root + directory as u64, &mut header)?;
+    let num_named = u16::from_le_bytes(header[12..14].try_into().unwrap()) as usize;
+    let num_ids = u16::from_le_bytes(header[14..16].try_into().unwrap()) as usize;
+
+    let mut entries = vec![0u8; (num_named + num_ids) * 8];
+    read_at(reader, root + directory as u64 + 16, &mut entries)?;
+
+    let entry = entries
+        .chunks_exact(8)
+        .map(|entry| {
+            (
+                u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+            )
+        })
+        .find(|&(name, _)| id.is_none_or(|id| name == id));
+
+    Ok(entry.map(|(_, offset)| offset))
+}
+
+/// One node of the `VS_VERSIONINFO` tree, given as ranges of the resource data
+struct Block {
+    key: String,
+    value: std::ops::Range<usize>,
+    /// `wType` 1, the value is UTF-16 text
+    is_text: bool,
+    children: std::ops::Range<usize>,
+    end: usize,
+}
+
+fn parse_version_info(data: &[u8]) -> Option<FileVersionInfo> {
+    let root = read_block(data, 0)?;
+    if root.key != "VS_VERSION_INFO" {
+        return None;
+    }
+
+    let mut info = FileVersionInfo::default();
+
+    // VS_FIXEDFILEINFO
+    let fixed = &data[root.value.clone()];
+    if fixed.len() >= 24 {
+        let field =
+            |offset: usize| u32::from_le_bytes(fixed[offset..offset + 4].try_into().unwrap());
+
+        if field(0) == FIXED_FILE_INFO_SIGNATURE {
+            info.fixed_file_version = Some(split_version(field(8), field(12)));
+            info.fixed_product_version = Some(split_version(field(16), field(20)));
+        }
+    }
+
+    for child in children(data, &root) {
+        if child.key != "StringFileInfo" {
+            continue;
+        }
+
+        // One string table per language, the first one that has a value wins
+        for table in children(data, &child) {
+            for string in children(data, &table) {
+                let value = if string.is_text {
+                    read_utf16(&data[string.value.clone()])
+                } else {
+                    String::new()
+                };
+                let value = value.trim().to_string();
+                if value.is_empty() {
+                    continue;
+                }
+
+                let field = match string.key.as_str() {
+                    "FileVersion" => &mut info.file_version,
+                    "ProductVersion" => &mut info.product_version,
+                    "CompanyName" => &mut info.company_name,
+                    "ProductName" => &mut info.product_name,
+                    "FileDescription" => &mut info.file_description,
+                    "LegalCopyright" => &mut info.legal_copyright,
+                    _ => continue,
+                };
+                field.get_or_insert(value);
+            }
+        }
+    }
+
+    Some(info)
+}
+
+fn read_block(data: &[u8], offset: usize) -> Option<Block> {
+    let word = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+
+    let length = word(offset)?;
+    let value_length = word(offset + 2)?;
+    let is_text = word(offset + 4)? == 1;
+
+    let end = offset + length;
+    if length < 6 || end > data.len() {
+        return None;
+    }
+
+    let key_start = offset + 6;
+    let key_len = data[key_start..end]
+        .chunks_exact(2)
+        .position(|char| char == [0, 0])?;
+    let key = read_utf16(&data[key_start..key_start + key_len * 2]);
+
+    // The value and the children start at 32-bit boundaries
+    let value_start = align(key_start + key_len * 2 + 2).min(end);
+    let value_size = if is_text {
+        value_length * 2
+    } else {
+        value_length
+    };
+    let value_end = (value_start + value_size).min(end);
+    let children_start = align(value_end).min(end);
+
+    Some(Block {
+        key,
+        value: value_start..value_end,
+        is_text,
+        children: children_start..end,
+        end,
+    })
+}
+
+fn children<'a>(data: &'a [u8], block: &Block) -> impl Iterator<Item = Block> + 'a {
+    let end = block.children.end;
+    let mut offset = block.children.start;
+
+    std::iter::from_fn(move || {
+        if offset >= end {
+            return None;
+        }
+
+        let child = read_block(&data[..end], offset)?;
+        offset = align(child.end);
+        Some(child)
+    })
+}
+
+fn read_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+fn split_version(most_significant: u32, least_significant: u32) -> [u16; 4] {
+    [
+        (most_significant >> 16) as u16,
+        most_significant as u16,
+        (least_significant >> 16) as u16,
+        least_significant as u16,
+    ]
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// Builds version resources for tests
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::super::MACHINE_AMD64;
+    use super::*;
+    use crate::arch::pe_fixture::{SECTION_RVA, image, put};
+
+    pub(super) enum Value<'a> {
+        None,
+        Binary(&'a [u8]),
+        Text(&'a str),
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    fn pad(bytes: &mut Vec<u8>) {
+        bytes.resize(align(bytes.len()), 0);
+    }
+
+    /// A `VS_VERSIONINFO` node with padding after the key, the value and between children
+    pub(super) fn block(key: &str, value: Value, children: &[Vec<u8>]) -> Vec<u8> {
+        let (value_length, is_text, value) = match value {
+            Value::None => (0, 0, Vec::new()),
+            Value::Binary(bytes) => (bytes.len(), 0, bytes.to_vec()),
+            Value::Text(text) => (text.encode_utf16().count() + 1, 1, utf16(text)),
+        };
+
+        let mut bytes = vec![0u8; 6];
+        bytes.extend(utf16(key));
+        pad(&mut bytes);
+        bytes.extend(value);
+
+        for child in children {
+            pad(&mut bytes);
+            bytes.extend(child);
+        }
+
+        let length = bytes.len() as u16;
+        bytes[0..2].copy_from_slice(&length.to_le_bytes());
+        bytes[2..4].copy_from_slice(&(value_length as u16).to_le_bytes());
+        bytes[4..6].copy_from_slice(&(is_text as u16).to_le_bytes());
+        bytes
+    }
+
+    pub(super) fn string(key: &str, value: &str) -> Vec<u8> {
+        block(key, Value::Text(value), &[])
+    }
+
+    fn fixed_file_info(file_version: [u32; 2], product_version: [u32; 2]) -> Vec<u8> {
+        let mut fixed = vec![0u8; 52];
+        put(&mut fixed, 0, &FIXED_FILE_INFO_SIGNATURE.to_le_bytes());
+        put(&mut fixed, 4, &0x0001_0000u32.to_le_bytes());
+        for (offset, part) in [8, 12, 16, 20]
+            .into_iter()
+            .zip(file_version.into_iter().chain(product_version))
+        {
+            put(&mut fixed, offset, &part.to_le_bytes());
+        }
+        fixed
+    }
+
+    pub(super) fn version_info_data() -> Vec<u8> {
+        let fixed = fixed_file_info([0x0001_0002, 0x0003_0004], [0x0001_0002, 0]);
+
+        let english = block(
+            "040904b0",
+            Value::None,
+            &[
+                string("CompanyName", "Acme"),
+                string("FileDescription", "Acme Synth VST"),
+                string("FileVersion", " 1.2.3.4 "),
+                string("ProductVersion", ""),
+                string("ProductName", "Synth"),
+                string("LegalCopyright", "(c) Acme"),
+                string("InternalName", "synth"),
+            ],
+        );
+        let german = block(
+            "040704b0",
+            Value::None,
+            &[
+                string("ProductName", "Synthesizer"),
+                string("ProductVersion", "1.2"),
+            ],
+        );
+        let translation = block(
+            "VarFileInfo",
+            Value::None,
+            &[block("Translation", Value::Binary(&[9, 4, 0xb0, 4]), &[])],
+        );
+
+        block(
+            "VS_VERSION_INFO",
+            Value::Binary(&fixed),
+            &[
+                block("StringFileInfo", Value::None, &[english, german]),
+                translation,
+            ],
+        )
+    }
+
+    pub(crate) fn expected_version_info() -> FileVersionInfo {
+        FileVersionInfo {
+            file_version: Some("1.2.3.4".to_string()),
+            product_version: Some("1.2".to_string()),
+            company_name: Some("Acme".to_string()),
+            product_name: Some("Synth".to_string()),
+            file_description: Some("Acme Synth VST".to_string()),
+            legal_copyright: Some("(c) Acme".to_string()),
+            fixed_file_version: Some([1, 2, 3, 4]),
+            fixed_product_version: Some([1, 2, 0, 0]),
+        }
+    }
+
+    /// A resource section with `data` as the only version resource
+    pub(super) fn resources(data: &[u8]) -> Vec<u8> {
+        let mut section = Vec::new();
+
+        // Type, name and language directories, each with one ID entry
+        for (directory, id, offset) in [
+            (0x00, RT_VERSION, SUBDIRECTORY | 0x18),
+            (0x18, 1, SUBDIRECTORY | 0x30),
+            (0x30, 0x409, 0x48),
+        ] {
+            put(&mut section, directory, &[0; 16]);
+            put(&mut section, directory + 14, &1u16.to_le_bytes());
+            put(&mut section, directory + 16, &id.to_le_bytes());
+            put(&mut section, directory + 20, &offset.to_le_bytes());
+        }
+
+        put(&mut section, 0x48, &(SECTION_RVA + 0x58).to_le_bytes());
+        put(&mut section, 0x4c, &(data.len() as u32).to_le_bytes());
+        put(&mut section, 0x58, data);
+        section
+    }
+
+    /// A 64-bit DLL whose version resource holds [`expected_version_info`]
+    pub(crate) fn dll_with_version_info() -> Vec<u8> {
+        let section = resources(&version_info_data());
+        let size = section.len() as u32;
+
+        image(
+            MACHINE_AMD64,
+            true,
+            &section,
+            &[(DIRECTORY_RESOURCE, 0, size)],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::super::MACHINE_AMD64;
+    use super::fixture::*;
+    use super::*;
+    use crate::arch::pe_fixture::{image, put};
+
+    #[test]
+    fn parses_fixed_and_string_file_info() {
+        assert_eq!(
+            parse_version_info(&version_info_data()),
+            Some(expected_version_info())
+        );
+    }
+
+    #[test]
+    fn missing_fixed_file_info() {
+        let data = block(
+            "VS_VERSION_INFO",
+            Value::None,
+            &[block(
+                "StringFileInfo",
+                Value::None,
+                &[block(
+                    "040904b0",
+                    Value::None,
+                    &[string("CompanyName", "A")],
+                )],
+            )],
+        );
+
+        let info = parse_version_info(&data).unwrap();
+        assert_eq!(info.company_name.as_deref(), Some("A"));
+        assert_eq!(info.fixed_file_version, None);
+    }
+
+    #[test]
+    fn rejects_other_roots_and_truncated_data() {
+        let data = version_info_data();
+
+        assert_eq!(parse_version_info(&data[..data.len() - 1]), None);
+        assert_eq!(parse_version_info(&data[..4]), None);
+        assert_eq!(parse_version_info(&[]), None);
+        assert_eq!(
+            parse_version_info(&block("VS_VERSION", Value::None, &[])),
+            None
+        );
+    }
+
+    #[test]
+    fn follows_resource_tree() {
+        assert_eq!(
+            version_info(&mut Cursor::new(dll_with_version_info())).unwrap(),
+            Some(expected_version_info())
+        );
+    }
+
+    #[test]
+    fn no_version_resource() {
+        let bytes = image(MACHINE_AMD64, true, &[0; 16], &[]);
+        assert_eq!(version_info(&mut Cursor::new(bytes)).unwrap(), None);
+
+        // A resource directory without an RT_VERSION entry
+        let mut section = resources(&version_info_data());
+        put(&mut section, 16, &3u32.to_le_bytes());
+        let size = section.len() as u32;
+        let bytes = image(
+            MACHINE_AMD64,
+            true,
+            &section,
+            &[(DIRECTORY_RESOURCE, 0, size)],
+        );
+        assert_eq!(version_info(&mut Cursor::new(bytes)).unwrap(), None);
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(audio_plugin_metadata::isolated::run_helper());
+}
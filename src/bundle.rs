@@ -0,0 +1,189 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::arch::BinaryArch;
+use crate::lib_loader::{PluginLoadError, check_arch};
+
+pub mod plist;
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Bundle {} has no binary for {} (looked in {})", .bundle.display(), .platform, .searched.join(", "))]
+    NoBinaryForPlatform {
+        bundle: PathBuf,
+        platform: String,
+        searched: Vec<String>,
+    },
+    #[error("Bundle only has a binary for {plugin}, host is {host}")]
+    WrongArchitecture {
+        plugin: BinaryArch,
+        host: BinaryArch,
+    },
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Plugin formats that are packaged as directories and should be handled as a single unit
+pub const BUNDLE_EXTENSIONS: &[&str] = &["vst3", "clap", "lv2", "component"];
+
+#[cfg(all(windows, target_arch = "x86_64"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["x86_64-win"];
+#[cfg(all(windows, target_arch = "x86"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["x86-win"];
+#[cfg(all(windows, target_arch = "aarch64"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["arm64-win", "arm64x-win"];
+#[cfg(all(windows, target_arch = "arm64ec"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["arm64ec-win", "arm64x-win", "x86_64-win"];
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["x86_64-linux"];
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["i386-linux", "i686-linux"];
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["aarch64-linux"];
+#[cfg(all(target_os = "linux", target_arch = "arm"))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["armv7l-linux", "armv7a-linux"];
+#[cfg(target_os = "macos")]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &["MacOS"];
+// No layout is defined for other platforms, their bundles are reported as having no binary
+#[cfg(not(any(
+    all(
+        windows,
+        any(
+            target_arch = "x86_64",
+            target_arch = "x86",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )
+    ),
+    all(
+        target_os = "linux",
+        any(
+            target_arch = "x86_64",
+            target_arch = "x86",
+            target_arch = "aarch64",
+            target_arch = "arm"
+        )
+    ),
+    target_os = "macos"
+)))]
+pub(crate) const VST3_ARCH_DIRS: &[&str] = &[];
+
+// Every architecture directory of this OS, to tell bundles for another architecture from broken
+// ones
+#[cfg(windows)]
+const VST3_OS_ARCH_DIRS: &[&str] = &[
+    "x86_64-win",
+    "x86-win",
+    "arm64-win",
+    "arm64x-win",
+    "arm64ec-win",
+];
+#[cfg(target_os = "linux")]
+const VST3_OS_ARCH_DIRS: &[&str] = &[
+    "x86_64-linux",
+    "i386-linux",
+    "i686-linux",
+    "aarch64-linux",
+    "armv7l-linux",
+    "armv7a-linux",
+];
+#[cfg(not(any(windows, target_os = "linux")))]
+const VST3_OS_ARCH_DIRS: &[&str] = &[];
+
+#[cfg(windows)]
+pub(crate) const VST3_BINARY_EXTENSION: Option<&str> = Some("vst3");
+#[cfg(target_os = "linux")]
+pub(crate) const VST3_BINARY_EXTENSION: Option<&str> = Some("so");
+#[cfg(target_os = "macos")]
+pub(crate) const VST3_BINARY_EXTENSION: Option<&str> = None;
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub(crate) const VST3_BINARY_EXTENSION: Option<&str> = Some("so");
+
+pub fn is_bundle(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                BUNDLE_EXTENSIONS
+                    .iter()
+                    .any(|bundle| bundle.eq_ignore_ascii_case(ext))
+            })
+}
+
+/// Finds the loadable module for the current OS and architecture. A plain file is
+/// returned as is, since older Windows plugins ship a single `.vst3` DLL.
+pub fn resolve_vst3_binary(path: &Path) -> Result<PathBuf, BundleError> {
+    if !path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+
+    let contents = path.join("Contents");
+    let stem = path.file_stem().unwrap_or_default();
+
+    for arch_dir in VST3_ARCH_DIRS {
+        if let Some(binary) = find_binary(&contents.join(arch_dir), stem)? {
+            return Ok(binary);
+        }
+    }
+
+    // A bundle built only for another architecture of this OS needs a bridge, not a fix
+    let foreign_dirs = VST3_OS_ARCH_DIRS
+        .iter()
+        .filter(|dir| !VST3_ARCH_DIRS.contains(dir));
+
+    for arch_dir in foreign_dirs {
+        let Some(binary) = find_binary(&contents.join(arch_dir), stem)? else {
+            continue;
+        };
+
+        match check_arch(&binary) {
+            Ok(()) => return Ok(binary),
+            Err(PluginLoadError::WrongArchitecture { plugin, host }) => {
+                return Err(BundleError::WrongArchitecture { plugin, host });
+            }
+            Err(_) => {}
+        }
+    }
+
+    Err(BundleError::NoBinaryForPlatform {
+        bundle: path.to_path_buf(),
+        platform: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+        searched: VST3_ARCH_DIRS
+            .iter()
+            .map(|dir| format!("Contents/{dir}"))
+            .collect(),
+    })
+}
+
+fn find_binary(dir: &Path, stem: &OsStr) -> Result<Option<PathBuf>, std::io::Error> {
+    let mut expected = dir.join(stem);
+    if let Some(ext) = VST3_BINARY_EXTENSION {
+        expected.set_extension(ext);
+    }
+
+    if expected.is_file() {
+        return Ok(Some(expected));
+    }
+
+    // The binary is usually named after the bundle, but renamed bundles are common
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let candidate = entry?.path();
+            let ext_matches = match VST3_BINARY_EXTENSION {
+                Some(ext) => candidate
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(ext)),
+                None => candidate.extension().is_none(),
+            };
+
+            if ext_matches && candidate.is_file() {
+                return Ok(Some(candidate));
+            }
+        }
+    }
+
+    Ok(None)
+}
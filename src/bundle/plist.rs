@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone)]
+pub struct InfoPlist {
+    pub name: Option<String>,
+    pub identifier: Option<String>,
+    pub short_version: Option<String>,
+    pub version: Option<String>,
+    pub copyright: Option<String>,
+}
+
+/// Reads `Contents/Info.plist` from a bundle. Only XML plists are understood, binary ones are
+/// reported as `None` the same way as a missing file.
+pub fn read_info_plist(bundle: &Path) -> Result<Option<InfoPlist>, std::io::Error> {
+    let path = bundle.join("Contents").join("Info.plist");
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(b"bplist") {
+        return Ok(None);
+    }
+
+    Ok(Some(parse_info_plist(&String::from_utf8_lossy(&bytes))))
+}
+
+pub fn parse_info_plist(text: &str) -> InfoPlist {
+    let mut plist = InfoPlist::default();
+    let mut rest = text;
+    let mut depth = 0;
+
+    while let Some((key, after_key)) = next_element(rest, "key") {
+        depth += nesting(&rest[..rest.len() - after_key.len()]);
+        rest = after_key;
+
+        // Keys of nested dictionaries, like those of document types, don't describe the bundle
+        if depth != 1 {
+            continue;
+        }
+
+        let trimmed = after_key.trim_start();
+        if !trimmed.starts_with("<string>") {
+            continue;
+        }
+
+        let Some((value, after_value)) = next_element(trimmed, "string") else {
+            break;
+        };
+        rest = after_value;
+
+        let value = Some(unescape(value));
+        match unescape(key).as_str() {
+            "CFBundleName" => plist.name = value,
+            "CFBundleIdentifier" => plist.identifier = value,
+            "CFBundleShortVersionString" => plist.short_version = value,
+            "CFBundleVersion" => plist.version = value,
+            "NSHumanReadableCopyright" => plist.copyright = value,
+            _ => {}
+        }
+    }
+
+    plist
+}
+
+fn next_element<'a>(text: &'a str, tag: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+
+    let start = text.find(&open)? + open.len();
+    let end = start + text[start..].find(&close)?;
+
+    Some((text[start..end].trim(), &text[end + close.len()..]))
+}
+
+// How many more dictionaries are opened than closed in `text`
+fn nesting(text: &str) -> isize {
+    text.matches("<dict>").count() as isize - text.matches("</dict>").count() as isize
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest
+            .find(';')
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+
+        // A stray `&` is kept as it is
+        match decoded {
+            Some((char, end)) => {
+                result.push(char);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "amp" => Some('&'),
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As shipped in a VST3 bundle built with the SDK's CMake templates, plus document types
+    const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleDevelopmentRegion</key>
+	<string>English</string>
+	<key>CFBundleExecutable</key>
+	<string>AGain</string>
+	<key>CFBundleIdentifier</key>
+	<string>com.steinberg.vst3.again</string>
+	<key>CFBundleInfoDictionaryVersion</key>
+	<string>6.0</string>
+	<key>CFBundleName</key>
+	<string>AGain &amp; Co</string>
+	<key>CFBundlePackageType</key>
+	<string>BNDL</string>
+	<key>CFBundleDocumentTypes</key>
+	<array>
+		<dict>
+			<key>CFBundleTypeName</key>
+			<string>Preset</string>
+			<key>CFBundleName</key>
+			<string>Nested</string>
+			<key>LSItemContentTypes</key>
+			<dict>
+				<key>CFBundleVersion</key>
+				<string>0.0</string>
+			</dict>
+		</dict>
+	</array>
+	<key>CFBundleShortVersionString</key>
+	<string>3.7.9</string>
+	<key>CFBundleSignature</key>
+	<string>????</string>
+	<key>CFBundleVersion</key>
+	<string> 3.7.9.0 </string>
+	<key>CSResourcesFileMapped</key>
+	<true/>
+	<key>NSHumanReadableCopyright</key>
+	<string>Copyright &#169; 2023 Steinberg &lt;info@steinberg.de&gt;</string>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn parses_top_level_keys() {
+        let plist = parse_info_plist(INFO_PLIST);
+
+        assert_eq!(plist.name.as_deref(), Some("AGain & Co"));
+        assert_eq!(
+            plist.identifier.as_deref(),
+            Some("com.steinberg.vst3.again")
+        );
+        assert_eq!(plist.short_version.as_deref(), Some("3.7.9"));
+        assert_eq!(plist.version.as_deref(), Some("3.7.9.0"));
+        assert_eq!(
+            plist.copyright.as_deref(),
+            Some("Copyright \u{a9} 2023 Steinberg <info@steinberg.de>")
+        );
+    }
+
+    #[test]
+    fn keys_with_entities() {
+        let text = "<plist><dict>\
+            <key>CFBundle&#78;ame</key><string>&quot;Synth&quot; &apos;1&apos; &#x263A;</string>\
+            <key>CFBundleIdentifier</key><string>a&b &unknown; &#xZZ;</string>\
+            </dict></plist>";
+        let plist = parse_info_plist(text);
+
+        assert_eq!(plist.name.as_deref(), Some("\"Synth\" '1' \u{263a}"));
+        assert_eq!(plist.identifier.as_deref(), Some("a&b &unknown; &#xZZ;"));
+    }
+
+    #[test]
+    fn missing_executable() {
+        let text = INFO_PLIST.replace(
+            "<key>CFBundleExecutable</key>\n\t<string>AGain</string>\n",
+            "",
+        );
+        assert!(!text.contains("CFBundleExecutable"));
+
+        let plist = parse_info_plist(&text);
+        assert_eq!(
+            plist.identifier.as_deref(),
+            Some("com.steinberg.vst3.again")
+        );
+        assert_eq!(plist.version.as_deref(), Some("3.7.9.0"));
+    }
+
+    #[test]
+    fn missing_or_malformed_values() {
+        let plist = parse_info_plist(
+            "<plist><dict><key>CFBundleName</key><integer>1</integer>\
+             <key>CFBundleVersion</key><string>1.0",
+        );
+        assert!(plist.name.is_none());
+        assert!(plist.version.is_none());
+
+        let plist = parse_info_plist("");
+        assert!(plist.name.is_none() && plist.identifier.is_none());
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use bincode::{Decode, Encode};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{bundle::resolve_vst3_binary, types::PluginInfo};
+
+// Bump whenever the encoded layout of `PluginInfo` changes
+const CACHE_VERSION: u32 = 10;
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Cannot encode scan cache: {0}")]
+    EncodeError(#[from] bincode::error::EncodeError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+struct Fingerprint {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+#[derive(Debug, Encode, Decode)]
+struct CacheEntry {
+    fingerprint: Fingerprint,
+    hash: Option<u64>,
+    info: PluginInfo,
+}
+
+// Written as a plain tuple by `save`, so entries don't have to be cloned
+#[derive(Debug, Decode)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<(PathBuf, CacheEntry)>,
+}
+
+/// Scan results of previous runs. An entry is reused as long as the plugin binary keeps its
+/// size and modification time. With `hash_contents` enabled, a file that was only touched or
+/// copied over with identical contents is recognised by its hash as well.
+#[derive(Debug, Default)]
+pub struct ScanCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    hash_contents: bool,
+}
+
+impl ScanCache {
+    pub fn new(hash_contents: bool) -> ScanCache {
+        ScanCache {
+            entries: HashMap::new(),
+            hash_contents,
+        }
+    }
+
+    /// Loads a cache written by `save`. A missing, outdated or unreadable cache file gives an
+    /// empty cache, everything is simply rescanned.
+    pub fn load(path: &Path, hash_contents: bool) -> Result<ScanCache, CacheError> {
+        let mut cache = ScanCache::new(hash_contents);
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(err) => return Err(err.into()),
+        };
+
+        let decoded: Result<CacheFile, _> =
+            bincode::decode_from_std_read(&mut BufReader::new(file), bincode::config::standard());
+
+        match decoded {
+            Ok(file) if file.version == CACHE_VERSION => {
+                cache.entries = file.entries.into_iter().collect();
+            }
+            Ok(file) => debug!(
+                "Discarding scan cache {} of version {}",
+                path.display(),
+                file.version
+            ),
+            Err(err) => warn!("Discarding unreadable scan cache {}: {err}", path.display()),
+        }
+
+        Ok(cache)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), CacheError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(path, _)| *path);
+
+        // Write next to the target and rename, so an interrupted save can't corrupt the cache
+        let tmp = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            bincode::encode_into_std_write(
+                (CACHE_VERSION, entries),
+                &mut writer,
+                bincode::config::standard(),
+            )?;
+            io::Write::flush(&mut writer)?;
+        }
+        fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+
+    /// Returns the cached info if the plugin hasn't changed since it was stored
+    pub fn get(&mut self, plugin: &Path) -> Option<&PluginInfo> {
+        let fingerprint = fingerprint(plugin).ok()?;
+        let hash_contents = self.hash_contents;
+
+        let entry = self.entries.get_mut(&key(plugin))?;
+        if entry.fingerprint != fingerprint {
+            if !hash_contents || entry.fingerprint.size != fingerprint.size {
+                return None;
+            }
+
+            let hash = content_hash(plugin).ok()?;
+            if entry.hash != Some(hash) {
+                return None;
+            }
+
+            entry.fingerprint = fingerprint;
+        }
+
+        Some(&entry.info)
+    }
+
+    pub fn insert(&mut self, plugin: &Path, info: PluginInfo) -> io::Result<()> {
+        let fingerprint = fingerprint(plugin)?;
+        let hash = match self.hash_contents {
+            true => Some(content_hash(plugin)?),
+            false => None,
+        };
+
+        self.entries.insert(
+            key(plugin),
+            CacheEntry {
+                fingerprint,
+                hash,
+                info,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Returns the cached info, or runs `scan` and caches its result when the plugin is new
+    /// or has changed.
+    pub fn get_or_scan<E>(
+        &mut self,
+        plugin: &Path,
+        scan: impl FnOnce(&Path) -> Result<PluginInfo, E>,
+    ) -> Result<&PluginInfo, E> {
+        if self.get(plugin).is_none() {
+            debug!("Scan cache miss for {}", plugin.display());
+            let info = scan(plugin)?;
+
+            if let Err(err) = self.insert(plugin, info) {
+                warn!("Cannot cache {}: {err}", plugin.display());
+            }
+        }
+
+        Ok(&self.entries[&key(plugin)].info)
+    }
+
+    pub fn remove(&mut self, plugin: &Path) -> Option<PluginInfo> {
+        self.entries.remove(&key(plugin)).map(|entry| entry.info)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Drops entries of plugins that no longer exist on disk
+    pub fn prune(&mut self) {
+        self.entries.retain(|path, _| path.exists());
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &PluginInfo)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_path(), &entry.info))
+    }
+}
+
+// The same plugin reached through a relative path, from another directory or through a link
+// shares one entry. Plugins that are gone keep the path they were stored under.
+fn key(plugin: &Path) -> PathBuf {
+    fs::canonicalize(plugin)
+        .or_else(|_| std::path::absolute(plugin))
+        .unwrap_or_else(|_| plugin.to_path_buf())
+}
+
+// Bundles are fingerprinted by the binary that would actually be loaded
+fn binary_path(plugin: &Path) -> PathBuf {
+    if plugin.is_dir()
+        && let Ok(binary) = resolve_vst3_binary(plugin)
+    {
+        return binary;
+    }
+
+    plugin.to_path_buf()
+}
+
+fn fingerprint(plugin: &Path) -> io::Result<Fingerprint> {
+    let metadata = fs::metadata(binary_path(plugin))?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Ok(Fingerprint {
+        size: metadata.len(),
+        mtime_secs: mtime.as_secs(),
+        mtime_nanos: mtime.subsec_nanos(),
+    })
+}
+
+// FNV-1a, the hash has to stay stable across Rust versions since it's persisted
+fn content_hash(plugin: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(binary_path(plugin))?);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        for &byte in &buffer[..read] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::types::{PartialInfo, PluginFormat};
+
+    fn info(name: &str) -> PluginInfo {
+        let mut info = PartialInfo::new(PluginFormat::Vst2);
+        info.name = Some(name.to_string());
+        PluginInfo::Partial(info)
+    }
+
+    fn touch(path: &Path) {
+        let mtime = SystemTime::now() + Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("apm-cache-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join("plugin.so");
+        let cache_path = dir.join("cache.bin");
+        fs::write(&plugin, b"plugin binary").unwrap();
+
+        let mut cache = ScanCache::new(false);
+        cache.insert(&plugin, info("Synth")).unwrap();
+        cache.save(&cache_path).unwrap();
+
+        let mut cache = ScanCache::load(&cache_path, false).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&plugin).and_then(PluginInfo::name), Some("Synth"));
+
+        // Same contents, new modification time: only the hash recognises the file
+        touch(&plugin);
+        assert!(cache.get(&plugin).is_none());
+
+        let mut cache = ScanCache::new(true);
+        cache.insert(&plugin, info("Synth")).unwrap();
+        cache.save(&cache_path).unwrap();
+        let mut cache = ScanCache::load(&cache_path, true).unwrap();
+        touch(&plugin);
+        assert_eq!(cache.get(&plugin).and_then(PluginInfo::name), Some("Synth"));
+
+        // Changed contents miss even with hashing
+        fs::write(&plugin, b"plugin binary, rebuilt").unwrap();
+        assert!(cache.get(&plugin).is_none());
+
+        let scanned = cache
+            .get_or_scan(&plugin, |_| Ok::<_, ()>(info("Rebuilt")))
+            .unwrap();
+        assert_eq!(scanned.name(), Some("Rebuilt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_are_shared_by_equivalent_paths() {
+        let dir = std::env::temp_dir().join(format!("apm-cache-paths-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let plugin = dir.join("plugin.so");
+        fs::write(&plugin, b"plugin binary").unwrap();
+
+        let mut cache = ScanCache::new(false);
+        cache
+            .insert(&dir.join("sub").join("..").join("plugin.so"), info("Synth"))
+            .unwrap();
+        cache
+            .insert(&dir.join(".").join("plugin.so"), info("Synth"))
+            .unwrap();
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&plugin).and_then(PluginInfo::name), Some("Synth"));
+        let canonical = fs::canonicalize(&plugin).unwrap();
+        let (path, _) = cache.iter().next().unwrap();
+        assert_eq!(path, canonical);
+
+        // A plugin that is gone is still found under the path it was stored with
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(cache.remove(&canonical).is_some());
+    }
+
+    #[test]
+    fn outdated_or_unreadable_file_gives_empty_cache() {
+        let dir = std::env::temp_dir().join(format!("apm-cache-invalid-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join("cache.bin");
+
+        assert!(ScanCache::load(&cache_path, false).unwrap().is_empty());
+
+        fs::write(&cache_path, b"not a cache").unwrap();
+        assert!(ScanCache::load(&cache_path, false).unwrap().is_empty());
+
+        let outdated = bincode::encode_to_vec(
+            (CACHE_VERSION - 1, Vec::<(PathBuf, CacheEntry)>::new()),
+            bincode::config::standard(),
+        )
+        .unwrap();
+        fs::write(&cache_path, outdated).unwrap();
+        assert!(ScanCache::load(&cache_path, false).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+use crate::host::HostIdentity;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ScanMode {
+    /// Load the plugin and query it
+    #[default]
+    Full,
+    /// Only read files on disk, never execute plugin code
+    StaticOnly,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanConfig {
+    pub mode: ScanMode,
+    /// Scanner executable used by `scan_file_isolated`. Defaults to `plugin-scanner` next to
+    /// the current executable.
+    pub helper: Option<PathBuf>,
+    /// Time limit for a single plugin in `scan_file_isolated`. The scanner process is killed
+    /// once it runs out.
+    pub timeout: Option<Duration>,
+    /// What plugins are told about the host that loads them
+    pub host: HostIdentity,
+    /// Directories besides the plugin's own and the system ones where its DLL dependencies are
+    /// loaded from on Windows
+    pub search_paths: Vec<PathBuf>,
+}
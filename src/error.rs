@@ -0,0 +1,165 @@
+use std::{path::PathBuf, time::Duration};
+
+use bincode::{Decode, Encode};
+use thiserror::Error;
+
+use crate::{arch::BinaryArch, bundle::BundleError, lib_loader::PluginLoadError, phase::ScanPhase};
+
+#[derive(Debug, Error)]
+pub enum ScanError {
+    #[error("{} is not a plugin: {reason}", .path.display())]
+    NotAPlugin { path: PathBuf, reason: String },
+    #[error("Plugin is built for {plugin} and can't be loaded by a {host} host without a bridge")]
+    WrongArchitecture {
+        plugin: BinaryArch,
+        host: BinaryArch,
+    },
+    #[error("Cannot load the plugin: {os_message}")]
+    LoadFailed { os_message: String },
+    #[error("Cannot load the plugin, missing dependencies: {}", .dlls.join(", "))]
+    MissingDependencies { dlls: Vec<String> },
+    #[error("The plugin exports none of {}", .symbols.join(", "))]
+    MissingEntryPoint { symbols: Vec<String> },
+    #[error("{entry} returned null")]
+    EntryReturnedNull { entry: String },
+    #[error("ModuleEntry failed")]
+    ModuleEntryFailed,
+    #[error("CoInitializeEx failed: HRESULT {result:#X}")]
+    ComInitFailed { result: i32 },
+    #[error("{method} failed with result {result:#X}")]
+    FactoryCallFailed { method: String, result: i32 },
+
+    #[error("Cannot start plugin scanner {}: {source}", .helper.display())]
+    HelperSpawnFailed {
+        helper: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Plugin scanner crashed: {description} ({code:#X})")]
+    Crashed { code: i32, description: String },
+    #[error("Plugin didn't finish the {phase} phase within {timeout:?}")]
+    Timeout { phase: ScanPhase, timeout: Duration },
+    #[error("Plugin scanner aborted")]
+    Aborted,
+    #[error("Plugin scanner exited with code {0}")]
+    ExitCode(i32),
+    #[error("Invalid response from plugin scanner: {0}")]
+    Protocol(String),
+    #[error("{0}")]
+    Plugin(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<PluginLoadError> for ScanError {
+    fn from(err: PluginLoadError) -> Self {
+        match err {
+            PluginLoadError::CannotOpenAsDataFile(os_message)
+            | PluginLoadError::LoadFailed(os_message) => ScanError::LoadFailed { os_message },
+            PluginLoadError::IoError(err) => ScanError::Io(err),
+            PluginLoadError::InvalidPeFormat(reason) => ScanError::LoadFailed {
+                os_message: format!("Invalid PE format: {reason}"),
+            },
+            PluginLoadError::WrongArchitecture { plugin, host } => {
+                ScanError::WrongArchitecture { plugin, host }
+            }
+            PluginLoadError::MissingDependencies { dlls } => {
+                ScanError::MissingDependencies { dlls }
+            }
+        }
+    }
+}
+
+impl From<BundleError> for ScanError {
+    fn from(err: BundleError) -> Self {
+        match err {
+            BundleError::NoBinaryForPlatform { ref bundle, .. } => ScanError::NotAPlugin {
+                path: bundle.clone(),
+                reason: err.to_string(),
+            },
+            BundleError::WrongArchitecture { plugin, host } => {
+                ScanError::WrongArchitecture { plugin, host }
+            }
+            BundleError::IoError(err) => ScanError::Io(err),
+        }
+    }
+}
+
+/// The errors of an in-process scan as they are sent back by the plugin scanner
+#[derive(Debug, Encode, Decode)]
+pub enum WireError {
+    NotAPlugin {
+        path: PathBuf,
+        reason: String,
+    },
+    WrongArchitecture {
+        plugin: BinaryArch,
+        host: BinaryArch,
+    },
+    LoadFailed {
+        os_message: String,
+    },
+    MissingDependencies {
+        dlls: Vec<String>,
+    },
+    MissingEntryPoint {
+        symbols: Vec<String>,
+    },
+    EntryReturnedNull {
+        entry: String,
+    },
+    ModuleEntryFailed,
+    ComInitFailed {
+        result: i32,
+    },
+    FactoryCallFailed {
+        method: String,
+        result: i32,
+    },
+    Io(String),
+    Other(String),
+}
+
+impl From<ScanError> for WireError {
+    fn from(err: ScanError) -> Self {
+        match err {
+            ScanError::NotAPlugin { path, reason } => WireError::NotAPlugin { path, reason },
+            ScanError::WrongArchitecture { plugin, host } => {
+                WireError::WrongArchitecture { plugin, host }
+            }
+            ScanError::LoadFailed { os_message } => WireError::LoadFailed { os_message },
+            ScanError::MissingDependencies { dlls } => WireError::MissingDependencies { dlls },
+            ScanError::MissingEntryPoint { symbols } => WireError::MissingEntryPoint { symbols },
+            ScanError::EntryReturnedNull { entry } => WireError::EntryReturnedNull { entry },
+            ScanError::ModuleEntryFailed => WireError::ModuleEntryFailed,
+            ScanError::ComInitFailed { result } => WireError::ComInitFailed { result },
+            ScanError::FactoryCallFailed { method, result } => {
+                WireError::FactoryCallFailed { method, result }
+            }
+            ScanError::Io(err) => WireError::Io(err.to_string()),
+            err => WireError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<WireError> for ScanError {
+    fn from(err: WireError) -> Self {
+        match err {
+            WireError::NotAPlugin { path, reason } => ScanError::NotAPlugin { path, reason },
+            WireError::WrongArchitecture { plugin, host } => {
+                ScanError::WrongArchitecture { plugin, host }
+            }
+            WireError::LoadFailed { os_message } => ScanError::LoadFailed { os_message },
+            WireError::MissingDependencies { dlls } => ScanError::MissingDependencies { dlls },
+            WireError::MissingEntryPoint { symbols } => ScanError::MissingEntryPoint { symbols },
+            WireError::EntryReturnedNull { entry } => ScanError::EntryReturnedNull { entry },
+            WireError::ModuleEntryFailed => ScanError::ModuleEntryFailed,
+            WireError::ComInitFailed { result } => ScanError::ComInitFailed { result },
+            WireError::FactoryCallFailed { method, result } => {
+                ScanError::FactoryCallFailed { method, result }
+            }
+            WireError::Io(message) => ScanError::Io(std::io::Error::other(message)),
+            WireError::Other(message) => ScanError::Plugin(message),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+/// How the scanner presents itself to plugins. Some plugins change their behaviour, or refuse to
+/// load, depending on the host they see.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct HostIdentity {
+    pub vendor: String,
+    pub product: String,
+    /// Answer to `audioMasterGetVendorVersion`
+    pub version: i32,
+    /// Answer to `audioMasterVersion`, e.g. 2400 for VST 2.4
+    pub vst_version: i32,
+    /// `audioMasterCanDo` strings answered with yes, all others are answered with "don't know"
+    pub can_do: Vec<String>,
+}
+
+impl HostIdentity {
+    pub fn supports(&self, can_do: &str) -> bool {
+        self.can_do.iter().any(|supported| supported == can_do)
+    }
+}
+
+impl Default for HostIdentity {
+    fn default() -> Self {
+        HostIdentity {
+            vendor: "MyHost".to_string(),
+            product: "MyHostProduct".to_string(),
+            version: 1000,
+            vst_version: 2100,
+            can_do: vec![],
+        }
+    }
+}
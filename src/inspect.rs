@@ -0,0 +1,217 @@
+use std::{ffi::OsStr, path::Path};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    arch::{detect_binary, read_pe_exports, read_pe_version_info},
+    bundle::{plist::read_info_plist, resolve_vst3_binary},
+    error::ScanError,
+    types::{PartialInfo, PluginFormat, PluginInfo},
+    vst2,
+    vst3::moduleinfo::read_moduleinfo,
+};
+
+/// Collects what can be learned about a plugin from the files on disk alone.
+/// No plugin code is loaded or executed.
+pub fn inspect_file(path: &Path) -> Result<PluginInfo, ScanError> {
+    info!("Going to inspect {}", path.display());
+
+    let format = detect_format(path)?;
+
+    if format == PluginFormat::Vst3 {
+        match read_moduleinfo(path) {
+            Ok(Some(mut vst3_info)) => {
+                // As in a full scan, the version resource of the binary is added
+                if let Ok(binary) = resolve_vst3_binary(path) {
+                    vst3_info.file_version_info = read_pe_version_info(&binary).ok().flatten();
+                }
+                return Ok(PluginInfo::Vst3(vst3_info));
+            }
+            Ok(None) => {}
+            Err(err) => warn!("Ignoring moduleinfo.json of {}: {err}", path.display()),
+        }
+    }
+
+    let binary = match format {
+        PluginFormat::Vst3 => resolve_vst3_binary(path)?,
+        PluginFormat::Vst2 => path.to_path_buf(),
+    };
+
+    let mut info = PartialInfo::new(format);
+    info.binary = Some(binary.display().to_string());
+    info.binary_info = detect_binary(&binary).ok();
+    info.arch = info
+        .binary_info
+        .as_ref()
+        .map(|binary| binary.preferred_arch());
+
+    if path.is_dir()
+        && let Some(plist) = read_info_plist(path)?
+    {
+        info.name = plist.name;
+        info.identifier = plist.identifier;
+        info.version = plist.short_version.or(plist.version);
+        info.copyright = plist.copyright;
+    }
+
+    info.file_version_info = read_pe_version_info(&binary).ok().flatten();
+    if let Some(version_info) = &info.file_version_info {
+        info.name = info.name.or_else(|| version_info.product_name.clone());
+        info.vendor = info.vendor.or_else(|| version_info.company_name.clone());
+        info.version = info.version.or_else(|| version_info.version());
+        info.copyright = info
+            .copyright
+            .or_else(|| version_info.legal_copyright.clone());
+    }
+
+    Ok(PluginInfo::Partial(info.finish()))
+}
+
+/// The format of the plugin at `path`. Windows DLLs are told apart by the entry points they
+/// export, so renamed files and helper DLLs are recognised. Anything else goes by its extension.
+pub(crate) fn detect_format(path: &Path) -> Result<PluginFormat, ScanError> {
+    let extension = path.extension();
+
+    if path.is_file() {
+        match read_pe_exports(path) {
+            Ok(exports) => return format_from_exports(path, &exports),
+            Err(err) => debug!("No exports read from {}: {err}", path.display()),
+        }
+    }
+
+    match extension {
+        Some(ext) if ext == OsStr::new("vst3") => Ok(PluginFormat::Vst3),
+        Some(ext) if ext == OsStr::new("dll") || ext == OsStr::new("so") => Ok(PluginFormat::Vst2),
+        _ => Err(ScanError::NotAPlugin {
+            path: path.to_path_buf(),
+            reason: "the file extension isn't one of 'vst3', 'dll', 'so'".to_string(),
+        }),
+    }
+}
+
+fn format_from_exports(path: &Path, exports: &[String]) -> Result<PluginFormat, ScanError> {
+    let exports_any =
+        |symbols: &[&str]| exports.iter().any(|name| symbols.contains(&name.as_str()));
+
+    let vst2 = exports_any(vst2::ENTRY_POINTS);
+    let vst3 = exports_any(&["GetPluginFactory"]);
+
+    info!(
+        "{} exports {} symbols, VST2 entry: {vst2}, VST3 entry: {vst3}",
+        path.display(),
+        exports.len()
+    );
+
+    match (vst2, vst3) {
+        // A DLL that is both keeps the format its extension says
+        (true, true) if path.extension() != Some(OsStr::new("vst3")) => Ok(PluginFormat::Vst2),
+        (_, true) => Ok(PluginFormat::Vst3),
+        (true, false) => Ok(PluginFormat::Vst2),
+        (false, false) => Err(ScanError::NotAPlugin {
+            path: path.to_path_buf(),
+            reason: if exports_any(&["clap_entry"]) {
+                "it is a CLAP plugin, which isn't supported".to_string()
+            } else {
+                "it exports no VST2 or VST3 entry point".to_string()
+            },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::arch::pe_fixture::{dll_exporting, dll_with_version_info, expected_version_info};
+    use crate::bundle::{VST3_ARCH_DIRS, VST3_BINARY_EXTENSION};
+    use crate::vst3::moduleinfo::moduleinfo_path;
+
+    /// Detects the format of a DLL named `name` exporting `exports`
+    fn detect(name: &str, exports: &[&str]) -> Result<PluginFormat, ScanError> {
+        let path = std::env::temp_dir().join(format!("apm-inspect-{}-{name}", process::id()));
+        fs::write(&path, dll_exporting(exports)).unwrap();
+
+        let format = detect_format(&path);
+        fs::remove_file(&path).unwrap();
+        format
+    }
+
+    #[test]
+    fn vst2_entry_points() {
+        assert_eq!(
+            detect("vst2.dll", &["VSTPluginMain"]).unwrap(),
+            PluginFormat::Vst2
+        );
+        assert_eq!(
+            detect("legacy.dll", &["DllMain", "main"]).unwrap(),
+            PluginFormat::Vst2
+        );
+    }
+
+    #[test]
+    fn vst3_entry_point_wins_over_extension() {
+        assert_eq!(
+            detect("renamed.dll", &["GetPluginFactory", "InitDll"]).unwrap(),
+            PluginFormat::Vst3
+        );
+        assert_eq!(
+            detect("plugin.vst3", &["GetPluginFactory"]).unwrap(),
+            PluginFormat::Vst3
+        );
+    }
+
+    #[test]
+    fn both_entry_points_follow_extension() {
+        let exports = ["VSTPluginMain", "GetPluginFactory"];
+        assert_eq!(detect("both.dll", &exports).unwrap(), PluginFormat::Vst2);
+        assert_eq!(detect("both.vst3", &exports).unwrap(), PluginFormat::Vst3);
+    }
+
+    #[test]
+    fn no_entry_point() {
+        assert!(matches!(
+            detect("helper.dll", &["DllMain"]),
+            Err(ScanError::NotAPlugin { .. })
+        ));
+
+        let Err(ScanError::NotAPlugin { reason, .. }) = detect("plugin.clap", &["clap_entry"])
+        else {
+            panic!("a CLAP plugin was accepted");
+        };
+        assert!(reason.contains("CLAP"));
+    }
+
+    #[test]
+    fn moduleinfo_bundle_keeps_version_info() {
+        let Some(arch_dir) = VST3_ARCH_DIRS.first() else {
+            return;
+        };
+
+        let root = std::env::temp_dir().join(format!("apm-inspect-bundle-{}", process::id()));
+        let bundle = root.join("Synth.vst3");
+        let binary_dir = bundle.join("Contents").join(arch_dir);
+        let mut binary = binary_dir.join("Synth");
+        if let Some(extension) = VST3_BINARY_EXTENSION {
+            binary.set_extension(extension);
+        }
+
+        fs::create_dir_all(&binary_dir).unwrap();
+        fs::create_dir_all(moduleinfo_path(&bundle).parent().unwrap()).unwrap();
+        fs::write(&binary, dll_with_version_info()).unwrap();
+        fs::write(
+            moduleinfo_path(&bundle),
+            r#"{ "Name": "Synth", "Factory Info": { "Vendor": "Acme" }, "Classes": [] }"#,
+        )
+        .unwrap();
+
+        let info = inspect_file(&bundle);
+        fs::remove_dir_all(&root).unwrap();
+
+        let Ok(PluginInfo::Vst3(info)) = info else {
+            panic!("moduleinfo.json wasn't used: {info:?}");
+        };
+        assert_eq!(info.factory_info.vendor, "Acme");
+        assert_eq!(info.file_version_info, Some(expected_version_info()));
+    }
+}
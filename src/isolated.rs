@@ -0,0 +1,507 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        Mutex, OnceLock, PoisonError,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bincode::{Decode, Encode};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{ScanConfig, ScanMode},
+    error::{ScanError, WireError},
+    host::HostIdentity,
+    phase::{ScanPhase, set_phase_observer},
+    scan_file_recorded,
+    trace::{ScanTrace, TraceEvent, TraceRecorder},
+    types::PluginInfo,
+};
+
+pub const HELPER_NAME: &str = "plugin-scanner";
+
+// Every message is framed with a marker and a length. Anything in between is skipped, in case
+// plugin output still finds its way into the stream.
+const FRAME_MAGIC: &[u8; 8] = b"APMSCAN\0";
+// Far above any real message, keeps a corrupt length from allocating gigabytes
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+// How long a scanner whose pipes broke gets to finish dying before it's killed
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+// The helper's end of the pipe to the host, taken over from stdout by `run_helper`
+static PROTOCOL: OnceLock<Mutex<File>> = OnceLock::new();
+
+#[derive(Debug, Encode, Decode)]
+pub struct ScanRequest {
+    pub path: PathBuf,
+    pub mode: ScanMode,
+    pub host: HostIdentity,
+    pub search_paths: Vec<PathBuf>,
+    /// Stream a `Trace` message for every call between the host and the plugin
+    pub trace: bool,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum ScanResponse {
+    /// Sent whenever the scanner moves on, so a hang can be attributed to a phase
+    Phase(ScanPhase),
+    /// Sent as the calls happen, so the trace survives a crash
+    Trace(TraceEvent),
+    Ok(Box<PluginInfo>),
+    Err(WireError),
+}
+
+/// Scans the plugin in a separate helper process, so a plugin that crashes or aborts can't take
+/// the calling process down with it.
+pub fn scan_file_isolated(path: &Path, config: &ScanConfig) -> Result<PluginInfo, ScanError> {
+    scan_in_helper(path, config, None)
+}
+
+/// Scans like `scan_file_isolated` and also returns every call between the host and the plugin,
+/// up to the point where the scanner crashed or was killed.
+pub fn scan_file_isolated_traced(
+    path: &Path,
+    config: &ScanConfig,
+) -> (Result<PluginInfo, ScanError>, ScanTrace) {
+    let mut trace = ScanTrace::default();
+    let result = scan_in_helper(path, config, Some(&mut trace));
+    (result, trace)
+}
+
+fn scan_in_helper(
+    path: &Path,
+    config: &ScanConfig,
+    mut trace: Option<&mut ScanTrace>,
+) -> Result<PluginInfo, ScanError> {
+    let helper = match &config.helper {
+        Some(helper) => helper.clone(),
+        None => default_helper_path()?,
+    };
+
+    info!("Going to scan {} with {}", path.display(), helper.display());
+    let mut child = Command::new(&helper)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|source| ScanError::HelperSpawnFailed {
+            helper: helper.clone(),
+            source,
+        })?;
+
+    let request = ScanRequest {
+        path: path.to_path_buf(),
+        mode: config.mode,
+        host: config.host.clone(),
+        search_paths: config.search_paths.clone(),
+        trace: trace.is_some(),
+    };
+
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(err) = write_frame(&mut stdin, &request)
+    {
+        return Err(abandon(&mut child, err));
+    }
+
+    let Some(stdout) = child.stdout.take() else {
+        let err = ScanError::Protocol("scanner has no stdout".to_string());
+        return Err(abandon(&mut child, err));
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdout = BufReader::new(stdout);
+        loop {
+            let message = read_frame::<ScanResponse>(&mut stdout);
+            let done = !matches!(
+                message,
+                Ok(Some(ScanResponse::Phase(_) | ScanResponse::Trace(_)))
+            );
+
+            if sender.send(message).is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let mut phase = ScanPhase::Load;
+
+    let response = loop {
+        let message = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match message {
+            Ok(Ok(Some(ScanResponse::Phase(next)))) => phase = next,
+            Ok(Ok(Some(ScanResponse::Trace(event)))) => {
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.events.push(event);
+                }
+            }
+            Ok(Ok(response)) => break Ok(response),
+            Ok(Err(err)) => break Err(err),
+            Err(RecvTimeoutError::Disconnected) => break Ok(None),
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("Scanning {} timed out in {phase}", path.display());
+
+                if let Err(err) = child.kill() {
+                    warn!("Cannot kill plugin scanner: {err}");
+                }
+                child.wait()?;
+
+                return Err(ScanError::Timeout {
+                    phase,
+                    timeout: config.timeout.unwrap_or_default(),
+                });
+            }
+        }
+    };
+
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => return Err(abandon(&mut child, err)),
+    };
+
+    let status = child.wait()?;
+    debug!("Plugin scanner exited with {status}");
+
+    // A plugin crashing while it's unloaded still delivered a usable result
+    match response {
+        Some(ScanResponse::Ok(info)) => Ok(*info),
+        Some(ScanResponse::Err(err)) => Err(err.into()),
+        Some(ScanResponse::Phase(_) | ScanResponse::Trace(_)) => {
+            unreachable!("phases and traces are consumed above")
+        }
+        None if status.success() => Err(ScanError::Protocol(
+            "scanner exited without sending a result".to_string(),
+        )),
+        None => Err(exit_error(status)),
+    }
+}
+
+/// Entry point of the helper executable. Reads one request from stdin, scans in-process and
+/// writes the response to what was stdout. Returns the process exit code.
+pub fn run_helper() -> i32 {
+    // Plugins print to stdout, from here on that ends up on stderr
+    match take_stdout() {
+        Ok(protocol) => {
+            PROTOCOL.get_or_init(|| Mutex::new(protocol));
+        }
+        Err(err) => {
+            eprintln!("{HELPER_NAME}: cannot redirect stdout: {err}");
+            return 2;
+        }
+    }
+
+    let request = match read_frame::<ScanRequest>(&mut io::stdin().lock()) {
+        Ok(Some(request)) => request,
+        Ok(None) => {
+            eprintln!("{HELPER_NAME}: no request on stdin");
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("{HELPER_NAME}: {err}");
+            return 2;
+        }
+    };
+
+    let config = ScanConfig {
+        mode: request.mode,
+        host: request.host,
+        search_paths: request.search_paths,
+        ..Default::default()
+    };
+
+    set_phase_observer(Some(Box::new(|phase| {
+        send_progress(&ScanResponse::Phase(phase))
+    })));
+
+    // Written right away instead of collected, the plugin may still crash the scanner
+    let recorder = request.trace.then(|| {
+        TraceRecorder::with_observer(|event| send_progress(&ScanResponse::Trace(event.clone())))
+    });
+
+    let response = match scan_file_recorded(&request.path, &config, recorder.as_ref()) {
+        Ok(info) => ScanResponse::Ok(Box::new(info)),
+        Err(err) => ScanResponse::Err(err.into()),
+    };
+    set_phase_observer(None);
+
+    if let Err(err) = send(&response) {
+        eprintln!("{HELPER_NAME}: {err}");
+        return 2;
+    }
+
+    0
+}
+
+fn send(response: &ScanResponse) -> Result<(), ScanError> {
+    let protocol = PROTOCOL
+        .get()
+        .ok_or_else(|| ScanError::Protocol("no connection to the host".to_string()))?;
+    let mut protocol = protocol.lock().unwrap_or_else(PoisonError::into_inner);
+
+    write_frame(&mut *protocol, response)?;
+    Ok(protocol.flush()?)
+}
+
+// Progress messages of the helper, losing one is no reason to give up on the scan
+fn send_progress(response: &ScanResponse) {
+    if let Err(err) = send(response) {
+        eprintln!("{HELPER_NAME}: {err}");
+    }
+}
+
+/// Duplicates stdout for the protocol and points stdout at stderr
+#[cfg(unix)]
+fn take_stdout() -> io::Result<File> {
+    use std::os::fd::AsFd;
+
+    let protocol = io::stdout().as_fd().try_clone_to_owned()?;
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(File::from(protocol))
+}
+
+/// Duplicates stdout for the protocol and points stdout at stderr. C runtimes that already
+/// opened stdout keep writing to the pipe, the frame markers get the host past that.
+#[cfg(windows)]
+fn take_stdout() -> io::Result<File> {
+    use std::os::windows::io::{AsHandle, AsRawHandle};
+    use windows_sys::Win32::System::Console::{STD_OUTPUT_HANDLE, SetStdHandle};
+
+    let protocol = io::stdout().as_handle().try_clone_to_owned()?;
+    if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, io::stderr().as_raw_handle()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(File::from(protocol))
+}
+
+fn default_helper_path() -> Result<PathBuf, ScanError> {
+    let exe = std::env::current_exe()?;
+    Ok(exe.with_file_name(format!("{HELPER_NAME}{}", std::env::consts::EXE_SUFFIX)))
+}
+
+fn write_frame<T: Encode>(writer: &mut impl Write, message: &T) -> Result<(), ScanError> {
+    let payload = bincode::encode_to_vec(message, bincode::config::standard())
+        .map_err(|err| ScanError::Protocol(err.to_string()))?;
+
+    writer.write_all(FRAME_MAGIC)?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+fn read_frame<T: Decode<()>>(reader: &mut impl Read) -> Result<Option<T>, ScanError> {
+    let mut matched = 0;
+    let mut byte = [0u8; 1];
+
+    while matched < FRAME_MAGIC.len() {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+
+        matched = if byte[0] == FRAME_MAGIC[matched] {
+            matched + 1
+        } else if byte[0] == FRAME_MAGIC[0] {
+            1
+        } else {
+            0
+        };
+    }
+
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(ScanError::Protocol(format!(
+            "frame of {len} bytes exceeds the limit of {MAX_FRAME_SIZE}"
+        )));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+
+    let (message, _) = bincode::decode_from_slice(&payload, bincode::config::standard())
+        .map_err(|err| ScanError::Protocol(err.to_string()))?;
+
+    Ok(Some(message))
+}
+
+/// Gets rid of a scanner after talking to it failed. A scanner that died explains the failure
+/// better with its exit status than the broken pipe does, one that is still running is killed.
+fn abandon(child: &mut Child, err: ScanError) -> ScanError {
+    let deadline = Instant::now() + EXIT_GRACE;
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            _ => break None,
+        }
+    };
+
+    match status {
+        Some(status) if !status.success() => exit_error(status),
+        Some(_) => err,
+        None => {
+            if let Err(kill_err) = child.kill() {
+                warn!("Cannot kill plugin scanner: {kill_err}");
+            }
+            if let Err(wait_err) = child.wait() {
+                warn!("Cannot wait for plugin scanner: {wait_err}");
+            }
+
+            err
+        }
+    }
+}
+
+fn exit_error(status: ExitStatus) -> ScanError {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            let description = match signal {
+                libc::SIGABRT => return ScanError::Aborted,
+                libc::SIGSEGV => "segmentation fault",
+                libc::SIGBUS => "bus error",
+                libc::SIGILL => "illegal instruction",
+                libc::SIGFPE => "floating point exception",
+                libc::SIGKILL => "killed",
+                _ => "terminated by signal",
+            };
+
+            return ScanError::Crashed {
+                code: signal,
+                description: description.to_string(),
+            };
+        }
+    }
+
+    match status.code() {
+        #[cfg(windows)]
+        Some(3) => ScanError::Aborted,
+        #[cfg(windows)]
+        Some(code) if code as u32 & 0xC000_0000 == 0xC000_0000 => {
+            let description = match code as u32 {
+                0xC000_0409 => return ScanError::Aborted,
+                0xC000_0005 => "access violation",
+                0xC000_001D => "illegal instruction",
+                0xC000_0094 => "integer division by zero",
+                0xC000_00FD => "stack overflow",
+                0xC000_0374 => "heap corruption",
+                _ => "unhandled exception",
+            };
+
+            ScanError::Crashed {
+                code,
+                description: description.to_string(),
+            }
+        }
+        Some(code) => ScanError::ExitCode(code),
+        None => ScanError::Crashed {
+            code: 0,
+            description: "terminated".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ScanRequest {
+        ScanRequest {
+            path: PathBuf::from("/plugins/Synth.vst3"),
+            mode: ScanMode::Full,
+            host: HostIdentity::default(),
+            search_paths: vec![PathBuf::from("/plugins/libs")],
+            trace: true,
+        }
+    }
+
+    fn frame(message: &ScanRequest) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, message).unwrap();
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> Result<Option<ScanRequest>, ScanError> {
+        read_frame(&mut &bytes[..])
+    }
+
+    #[test]
+    fn round_trip_past_plugin_output() {
+        let mut bytes = b"plugin says hello\nAPM".to_vec();
+        bytes.extend(frame(&request()));
+        bytes.extend(b"APMSCAN but not quite");
+        bytes.extend(frame(&request()));
+
+        let mut reader = &bytes[..];
+        for _ in 0..2 {
+            let received = read_frame::<ScanRequest>(&mut reader).unwrap().unwrap();
+            assert_eq!(received.path, request().path);
+            assert_eq!(received.search_paths, request().search_paths);
+            assert!(received.trace);
+        }
+        assert!(read_frame::<ScanRequest>(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_magic_is_never_a_frame() {
+        let mut bytes = frame(&request());
+        bytes[6] = b'M';
+
+        assert!(read(&bytes).unwrap().is_none());
+        assert!(read(b"").unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let bytes = frame(&request());
+
+        assert!(matches!(
+            read(&bytes[..bytes.len() - 1]),
+            Err(ScanError::Io(_))
+        ));
+        assert!(matches!(
+            read(&bytes[..FRAME_MAGIC.len() + 2]),
+            Err(ScanError::Io(_))
+        ));
+        assert!(read(&bytes[..FRAME_MAGIC.len() - 1]).unwrap().is_none());
+    }
+
+    #[test]
+    fn oversized_frame_is_not_allocated() {
+        let mut bytes = FRAME_MAGIC.to_vec();
+        bytes.extend(u32::MAX.to_le_bytes());
+
+        assert!(matches!(read(&bytes), Err(ScanError::Protocol(_))));
+    }
+
+    #[test]
+    fn undecodable_payload() {
+        let mut bytes = FRAME_MAGIC.to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend([0xff, 0xff]);
+
+        assert!(matches!(read(&bytes), Err(ScanError::Protocol(_))));
+    }
+}
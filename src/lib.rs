@@ -1,29 +1,82 @@
-use std::{error::Error, ffi::OsStr, path::Path};
+use std::path::Path;
 
-use types::PluginInfo;
+use arch::read_pe_version_info;
+use bundle::resolve_vst3_binary;
+use config::{ScanConfig, ScanMode};
+use error::ScanError;
+use inspect::{detect_format, inspect_file};
+pub use isolated::{scan_file_isolated, scan_file_isolated_traced};
+use lib_loader::check_arch;
+use trace::{ScanTrace, TraceRecorder};
+use tracing::warn;
+use types::{PluginFormat, PluginInfo};
 use vst2::scan_vst2;
-use vst3::scan_vst3;
+use vst3::{moduleinfo::read_moduleinfo, scan_vst3};
 
 pub mod arch;
+pub mod bundle;
+pub mod cache;
+pub mod config;
+pub mod error;
+pub mod host;
+pub mod inspect;
+pub mod isolated;
 pub mod lib_loader;
+pub mod phase;
 pub mod scan;
+pub mod trace;
 pub mod types;
 pub mod utils;
 pub mod vst2;
 pub mod vst3;
 
-pub fn scan_file(path: &Path) -> Result<PluginInfo, Box<dyn Error>> {
-    if let Some(ext) = path.extension() {
-        if ext == OsStr::new("vst3") {
-            let loader = scan_vst3(path)?;
-            let vst3_info = loader.read_info()?;
-            return Ok(PluginInfo::Vst3(vst3_info));
-        }
+pub fn scan_file(path: &Path) -> Result<PluginInfo, ScanError> {
+    scan_file_with(path, &ScanConfig::default())
+}
 
-        if ext == OsStr::new("dll") {
-            return scan_vst2(path).map(PluginInfo::Vst2);
-        }
+pub fn scan_file_with(path: &Path, config: &ScanConfig) -> Result<PluginInfo, ScanError> {
+    scan_file_recorded(path, config, None)
+}
+
+/// Scans like `scan_file_with` and also returns every call between the host and the plugin.
+/// Nothing is recorded when no plugin code runs, as for static scans.
+pub fn scan_file_traced(
+    path: &Path,
+    config: &ScanConfig,
+) -> (Result<PluginInfo, ScanError>, ScanTrace) {
+    let recorder = TraceRecorder::new();
+    let result = scan_file_recorded(path, config, Some(&recorder));
+    (result, recorder.trace())
+}
+
+pub(crate) fn scan_file_recorded(
+    path: &Path,
+    config: &ScanConfig,
+    trace: Option<&TraceRecorder>,
+) -> Result<PluginInfo, ScanError> {
+    if config.mode == ScanMode::StaticOnly {
+        return inspect_file(path);
     }
 
-    Err("The file extension isn't correct. Expected to be one of: 'vst3', 'dll'".into())
+    match detect_format(path)? {
+        PluginFormat::Vst3 => {
+            // moduleinfo.json is read without loading anything, the binary must still fit
+            let binary = resolve_vst3_binary(path)?;
+            check_arch(&binary)?;
+
+            let moduleinfo = read_moduleinfo(path).unwrap_or_else(|err| {
+                warn!("Ignoring moduleinfo.json of {}: {err}", path.display());
+                None
+            });
+
+            let mut vst3_info = match moduleinfo {
+                Some(vst3_info) => vst3_info,
+                None => scan_vst3(path, config, trace)?.read_info()?,
+            };
+            vst3_info.file_version_info = read_pe_version_info(&binary).ok().flatten();
+
+            Ok(PluginInfo::Vst3(vst3_info))
+        }
+        PluginFormat::Vst2 => scan_vst2(path, config, trace).map(PluginInfo::Vst2),
+    }
 }
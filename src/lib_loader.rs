@@ -1,17 +1,19 @@
 use libloading::Library;
-use std::os::windows::ffi::OsStrExt;
-use std::path::Path;
-use std::ptr::null_mut;
+use std::path::{Path, PathBuf};
+use tracing::debug;
 
-use windows_sys::Win32::Foundation::{FreeLibrary, GetLastError, LocalFree};
-use windows_sys::Win32::System::Diagnostics::Debug::{
-    FORMAT_MESSAGE_ALLOCATE_BUFFER, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
-    FormatMessageW,
-};
-use windows_sys::Win32::System::LibraryLoader::{
-    LOAD_LIBRARY_AS_DATAFILE, LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR, LoadLibraryExW,
-    SetDefaultDllDirectories,
-};
+use crate::arch::{BinaryArch, detect_binary};
+
+mod dependencies;
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+use unix as platform;
+#[cfg(windows)]
+use windows as platform;
 
 #[derive(Debug)]
 pub enum PluginLoadError {
@@ -19,6 +21,14 @@ pub enum PluginLoadError {
     LoadFailed(String),
     IoError(std::io::Error),
     InvalidPeFormat(String),
+    WrongArchitecture {
+        plugin: BinaryArch,
+        host: BinaryArch,
+    },
+    /// The plugin failed to load and these DLLs it imports are nowhere to be found
+    MissingDependencies {
+        dlls: Vec<String>,
+    },
 }
 
 impl std::fmt::Display for PluginLoadError {
@@ -30,56 +40,62 @@ impl std::fmt::Display for PluginLoadError {
             PluginLoadError::LoadFailed(err) => write!(f, "Load failed: {}", err),
             PluginLoadError::IoError(err) => write!(f, "IO error: {}", err),
             PluginLoadError::InvalidPeFormat(err) => write!(f, "Invalid PE format: {}", err),
+            PluginLoadError::WrongArchitecture { plugin, host } => {
+                write!(f, "Plugin is built for {}, host is {}", plugin, host)
+            }
+            PluginLoadError::MissingDependencies { dlls } => {
+                write!(f, "Missing dependencies: {}", dlls.join(", "))
+            }
         }
     }
 }
 
 impl std::error::Error for PluginLoadError {}
 
-pub fn load_dll(path: &Path) -> Result<Library, PluginLoadError> {
-    let wide_path = utf16_path(path);
+/// Loads a plugin binary, on Windows with its dependencies also looked up in `search_paths`. When
+/// that fails, its imports are looked up where the loader searched to tell which one is missing.
+pub fn load_dll(path: &Path, search_paths: &[PathBuf]) -> Result<Library, PluginLoadError> {
+    check_arch(path)?;
 
-    unsafe {
-        SetDefaultDllDirectories(LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR);
+    platform::load_dll(path, search_paths).map_err(|err| match err {
+        PluginLoadError::LoadFailed(_) => {
+            let dlls = dependencies::find_missing(path, search_paths);
 
-        let handle = LoadLibraryExW(wide_path.as_ptr(), null_mut(), LOAD_LIBRARY_AS_DATAFILE);
-        if handle.is_null() {
-            return Err(PluginLoadError::CannotOpenAsDataFile(last_error_message()));
-        } else {
-            FreeLibrary(handle);
+            if dlls.is_empty() {
+                err
+            } else {
+                PluginLoadError::MissingDependencies { dlls }
+            }
         }
-    }
-
-    unsafe { Library::new(path).map_err(|_| PluginLoadError::LoadFailed(last_error_message())) }
-}
-
-fn utf16_path(path: &Path) -> Vec<u16> {
-    path.as_os_str().encode_wide().chain(Some(0)).collect()
+        err => err,
+    })
 }
 
-fn last_error_message() -> String {
-    unsafe {
-        let error_code = GetLastError();
-        let mut buffer: *mut u16 = null_mut();
+// The loader's own error for a foreign binary is hardly understandable, so it's never asked
+pub(crate) fn check_arch(path: &Path) -> Result<(), PluginLoadError> {
+    let host = BinaryArch::host();
 
-        let len = FormatMessageW(
-            FORMAT_MESSAGE_ALLOCATE_BUFFER
-                | FORMAT_MESSAGE_FROM_SYSTEM
-                | FORMAT_MESSAGE_IGNORE_INSERTS,
-            null_mut(),
-            error_code,
-            0,
-            std::mem::transmute(&mut buffer),
-            0,
-            null_mut(),
-        );
-
-        if len == 0 || buffer.is_null() {
-            return format!("OS Error {}", error_code);
+    let binary = match detect_binary(path) {
+        Ok(binary) => binary,
+        Err(err) => {
+            debug!(
+                "Cannot detect the architecture of {}: {err}",
+                path.display()
+            );
+            return Ok(());
         }
+    };
 
-        let message = String::from_utf16_lossy(std::slice::from_raw_parts(buffer, len as usize));
-        LocalFree(buffer as _);
-        message.trim().to_string()
+    // Unknown architectures are left to the loader, they might still fit
+    if host == BinaryArch::Unknown
+        || binary.loadable_by(host)
+        || binary.contains(BinaryArch::Unknown)
+    {
+        return Ok(());
     }
+
+    Err(PluginLoadError::WrongArchitecture {
+        plugin: binary.preferred_arch(),
+        host,
+    })
 }
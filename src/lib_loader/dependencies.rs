@@ -0,0 +1,117 @@
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+use crate::arch::read_pe_imports;
+
+/// DLLs imported by `plugin`, directly or through the DLLs found next to it or in the
+/// `search_paths`, that can't be found anywhere. System DLLs are trusted to be complete.
+pub(super) fn find_missing(plugin: &Path, search_paths: &[PathBuf]) -> Vec<String> {
+    // Dependencies found here are checked for their own imports too
+    let local_dirs: Vec<PathBuf> = plugin
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(search_paths.iter().cloned())
+        .collect();
+    let system_dirs = system_dirs();
+
+    let mut missing = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = vec![plugin.to_path_buf()];
+
+    while let Some(binary) = queue.pop() {
+        let imports = match read_pe_imports(&binary) {
+            Ok(imports) => imports,
+            Err(err) => {
+                debug!("Cannot read the imports of {}: {err}", binary.display());
+                continue;
+            }
+        };
+
+        for dll in imports {
+            if !seen.insert(dll.to_ascii_lowercase()) || is_api_set(&dll) {
+                continue;
+            }
+
+            if let Some(found) = find_in(&local_dirs, &dll) {
+                queue.push(found);
+            } else if find_in(&system_dirs, &dll).is_none() {
+                missing.push(dll);
+            }
+        }
+    }
+
+    missing
+}
+
+fn find_in(dirs: &[PathBuf], dll: &str) -> Option<PathBuf> {
+    dirs.iter()
+        .map(|dir| dir.join(dll))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The other directories of `LOAD_LIBRARY_SEARCH_DEFAULT_DIRS`, the host's own and System32.
+/// `PATH` isn't searched when the plugin is loaded with these flags.
+fn system_dirs() -> Vec<PathBuf> {
+    let application_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let system32 = env::var_os("SystemRoot")
+        .or_else(|| env::var_os("windir"))
+        .map(|root| PathBuf::from(root).join("System32"));
+
+    application_dir.into_iter().chain(system32).collect()
+}
+
+// API set names are virtual, the loader maps them to system DLLs
+fn is_api_set(dll: &str) -> bool {
+    let dll = dll.to_ascii_lowercase();
+    dll.starts_with("api-ms-win-") || dll.starts_with("ext-ms-")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::arch::pe_fixture::dll_importing;
+
+    #[test]
+    fn finds_dependencies_next_to_plugin_and_in_search_paths() {
+        let root = env::temp_dir().join(format!("apm-dependencies-{}", process::id()));
+        let plugin_dir = root.join("plugin");
+        let search_dir = root.join("search");
+        fs::create_dir_all(&plugin_dir).unwrap();
+        fs::create_dir_all(&search_dir).unwrap();
+
+        let plugin = plugin_dir.join("plugin.dll");
+        let dlls = [
+            (
+                plugin.clone(),
+                dll_importing(&["bundled.dll", "extra.dll", "gone.dll"]),
+            ),
+            (
+                plugin_dir.join("bundled.dll"),
+                dll_importing(&["nested.dll", "api-ms-win-crt-runtime-l1-1-0.dll"]),
+            ),
+            (search_dir.join("extra.dll"), dll_importing(&["GONE.DLL"])),
+        ];
+        for (path, bytes) in dlls {
+            fs::write(path, bytes).unwrap();
+        }
+
+        let mut without_search_path = find_missing(&plugin, &[]);
+        let mut with_search_path = find_missing(&plugin, &[search_dir]);
+        fs::remove_dir_all(&root).unwrap();
+
+        without_search_path.sort();
+        assert_eq!(without_search_path, ["extra.dll", "gone.dll", "nested.dll"]);
+        with_search_path.sort();
+        assert_eq!(with_search_path, ["gone.dll", "nested.dll"]);
+    }
+}
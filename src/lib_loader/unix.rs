@@ -0,0 +1,39 @@
+use libloading::Library;
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::PluginLoadError;
+
+/// `search_paths` are left to `LD_LIBRARY_PATH`, the dynamic linker can't be told about them for
+/// a single `dlopen`
+pub fn load_dll(path: &Path, _search_paths: &[PathBuf]) -> Result<Library, PluginLoadError> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        PluginLoadError::LoadFailed(format!("Path contains a NUL byte: {}", path.display()))
+    })?;
+
+    unsafe {
+        // Drop any error left over from an earlier call, so that the message we report
+        // belongs to this `dlopen`.
+        libc::dlerror();
+
+        let handle = libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            return Err(PluginLoadError::LoadFailed(last_error_message()));
+        }
+
+        Ok(libloading::os::unix::Library::from_raw(handle).into())
+    }
+}
+
+fn last_error_message() -> String {
+    unsafe {
+        let message = libc::dlerror();
+
+        if message.is_null() {
+            return "Unknown dlopen error".to_string();
+        }
+
+        CStr::from_ptr(message).to_string_lossy().trim().to_string()
+    }
+}
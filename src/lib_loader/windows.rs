@@ -0,0 +1,110 @@
+use libloading::Library;
+use libloading::os::windows::{
+    LOAD_LIBRARY_SEARCH_DEFAULT_DIRS, LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR, Library as WindowsLibrary,
+};
+use std::collections::HashSet;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{self, Path, PathBuf};
+use std::ptr::null_mut;
+use std::sync::{LazyLock, Mutex};
+use tracing::warn;
+
+use windows_sys::Win32::Foundation::{FreeLibrary, GetLastError, LocalFree};
+use windows_sys::Win32::System::Diagnostics::Debug::{
+    FORMAT_MESSAGE_ALLOCATE_BUFFER, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+    FormatMessageW,
+};
+use windows_sys::Win32::System::LibraryLoader::{
+    AddDllDirectory, LOAD_LIBRARY_AS_DATAFILE, LoadLibraryExW,
+};
+
+use super::PluginLoadError;
+
+/// Directories already passed to `AddDllDirectory`, they stay registered for the whole process
+static REGISTERED_DIRS: LazyLock<Mutex<HashSet<PathBuf>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Loads the plugin, looking for its dependencies next to it, in `search_paths` and in the
+/// system directories
+pub fn load_dll(path: &Path, search_paths: &[PathBuf]) -> Result<Library, PluginLoadError> {
+    // The search flags only accept absolute paths
+    let path = path::absolute(path).map_err(PluginLoadError::IoError)?;
+    let wide_path = utf16_path(&path);
+
+    register_search_paths(search_paths);
+
+    unsafe {
+        let handle = LoadLibraryExW(wide_path.as_ptr(), null_mut(), LOAD_LIBRARY_AS_DATAFILE);
+        if handle.is_null() {
+            return Err(PluginLoadError::CannotOpenAsDataFile(last_error_message()));
+        } else {
+            FreeLibrary(handle);
+        }
+    }
+
+    unsafe {
+        WindowsLibrary::load_with_flags(
+            &path,
+            LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS,
+        )
+        .map(Library::from)
+        .map_err(|_| PluginLoadError::LoadFailed(last_error_message()))
+    }
+}
+
+fn register_search_paths(search_paths: &[PathBuf]) {
+    let mut registered = REGISTERED_DIRS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+
+    for dir in search_paths {
+        let Ok(dir) = path::absolute(dir) else {
+            continue;
+        };
+        if registered.contains(&dir) {
+            continue;
+        }
+
+        let cookie = unsafe { AddDllDirectory(utf16_path(&dir).as_ptr()) };
+        if cookie.is_null() {
+            warn!(
+                "Cannot add {} to the DLL search path: {}",
+                dir.display(),
+                last_error_message()
+            );
+        } else {
+            registered.insert(dir);
+        }
+    }
+}
+
+fn utf16_path(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+fn last_error_message() -> String {
+    unsafe {
+        let error_code = GetLastError();
+        let mut buffer: *mut u16 = null_mut();
+
+        let len = FormatMessageW(
+            FORMAT_MESSAGE_ALLOCATE_BUFFER
+                | FORMAT_MESSAGE_FROM_SYSTEM
+                | FORMAT_MESSAGE_IGNORE_INSERTS,
+            null_mut(),
+            error_code,
+            0,
+            std::mem::transmute(&mut buffer),
+            0,
+            null_mut(),
+        );
+
+        if len == 0 || buffer.is_null() {
+            return format!("OS Error {}", error_code);
+        }
+
+        let message = String::from_utf16_lossy(std::slice::from_raw_parts(buffer, len as usize));
+        LocalFree(buffer as _);
+        message.trim().to_string()
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use audio_plugin_metadata::{
+    cache::ScanCache,
+    config::{ScanConfig, ScanMode},
+    error::ScanError,
+    scan::{WalkOptions, scan_path_with},
+    scan_file_isolated, scan_file_isolated_traced, scan_file_traced, scan_file_with,
+    trace::ScanTrace,
+    types::PluginInfo,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+
+const EXTENSIONS: &[&str] = &["vst3", "dll", "so"];
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Reads the metadata of VST2 and VST3 plugins")]
+struct Cli {
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+
+    /// Scan cache file [default: the user's cache directory]
+    #[arg(long, global = true, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Ndjson,
+    Table,
+}
+
+#[derive(Debug, Args)]
+struct ScanArgs {
+    /// Only read files on disk, never run plugin code
+    #[arg(long)]
+    static_only: bool,
+
+    /// Scan inside this process instead of a separate scanner process
+    #[arg(long)]
+    in_process: bool,
+
+    /// Give up on a plugin after this many seconds. Only a separate scanner process can be
+    /// stopped, so this doesn't go with --in-process or --static-only.
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["in_process", "static_only"])]
+    timeout: Option<u64>,
+
+    /// Also load Windows plugin dependencies from this directory, can be repeated
+    #[arg(long = "search-path", value_name = "DIR")]
+    search_paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Scan directories for plugins
+    Scan {
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+
+        #[command(flatten)]
+        scan: ScanArgs,
+
+        /// How deep to descend into the directories
+        #[arg(long)]
+        max_depth: Option<usize>,
+
+        /// Follow symbolic links
+        #[arg(long)]
+        follow_links: bool,
+
+        /// Rescan every plugin, ignoring and not updating the cache
+        #[arg(long)]
+        no_cache: bool,
+    },
+    /// Show the metadata of a single plugin
+    Info {
+        plugin: PathBuf,
+
+        #[command(flatten)]
+        scan: ScanArgs,
+
+        /// Write every call between the host and the plugin to this file, as JSON
+        #[arg(long, value_name = "FILE")]
+        trace: Option<PathBuf>,
+    },
+    /// Inspect or maintain the scan cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// List the cached plugins
+    List,
+    /// Remove all cached plugins
+    Clear,
+    /// Scan every cached plugin again
+    Rescan {
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+}
+
+struct Scanner {
+    config: ScanConfig,
+    isolated: bool,
+}
+
+impl Scanner {
+    fn new(args: &ScanArgs) -> Scanner {
+        let config = ScanConfig {
+            mode: match args.static_only {
+                true => ScanMode::StaticOnly,
+                false => ScanMode::Full,
+            },
+            timeout: args.timeout.map(Duration::from_secs),
+            search_paths: args.search_paths.clone(),
+            ..Default::default()
+        };
+
+        Scanner {
+            config,
+            isolated: !args.in_process && !args.static_only,
+        }
+    }
+
+    fn scan(&self, path: &Path) -> Result<PluginInfo, ScanError> {
+        if self.isolated {
+            scan_file_isolated(path, &self.config)
+        } else {
+            scan_file_with(path, &self.config)
+        }
+    }
+
+    fn scan_traced(&self, path: &Path) -> (Result<PluginInfo, ScanError>, ScanTrace) {
+        if self.isolated {
+            scan_file_isolated_traced(path, &self.config)
+        } else {
+            scan_file_traced(path, &self.config)
+        }
+    }
+}
+
+struct Printer {
+    format: Format,
+    json: Vec<serde_json::Value>,
+    rows: Vec<[String; 5]>,
+}
+
+impl Printer {
+    fn new(format: Format) -> Printer {
+        Printer {
+            format,
+            json: vec![],
+            rows: vec![],
+        }
+    }
+
+    fn push(&mut self, path: &Path, result: Result<&PluginInfo, &ScanError>) -> io::Result<()> {
+        match self.format {
+            Format::Json => self.json.push(record(path, result)),
+            Format::Ndjson => {
+                let mut stdout = io::stdout().lock();
+                serde_json::to_writer(&mut stdout, &record(path, result))?;
+                writeln!(stdout)?;
+            }
+            Format::Table => self.rows.extend(rows(path, result)),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+
+        match self.format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut stdout, &self.json)?;
+                writeln!(stdout)?;
+            }
+            Format::Ndjson => {}
+            Format::Table => {
+                let header = ["FORMAT", "NAME", "VENDOR", "STATUS", "PATH"].map(String::from);
+                let rows: Vec<_> = std::iter::once(header).chain(self.rows).collect();
+
+                let mut widths = [0; 5];
+                for row in &rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(cell.chars().count());
+                    }
+                }
+
+                for row in &rows {
+                    let line = row
+                        .iter()
+                        .zip(widths)
+                        .map(|(cell, width)| format!("{cell:width$}"))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    writeln!(stdout, "{}", line.trim_end())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn record(path: &Path, result: Result<&PluginInfo, &ScanError>) -> serde_json::Value {
+    match result {
+        Ok(info) => serde_json::json!({ "path": path, "info": info }),
+        Err(ScanError::WrongArchitecture { plugin, host }) => serde_json::json!({
+            "path": path,
+            "needs_bridge": { "plugin": plugin, "host": host },
+        }),
+        Err(error) => serde_json::json!({ "path": path, "error": error.to_string() }),
+    }
+}
+
+fn rows(path: &Path, result: Result<&PluginInfo, &ScanError>) -> Vec<[String; 5]> {
+    let path = path.display().to_string();
+
+    let info = match result {
+        Ok(info) => info,
+        Err(error) => {
+            // Plugins for another architecture work fine in a host with a bridge
+            let status = match error {
+                ScanError::WrongArchitecture { plugin, .. } => format!("needs bridge ({plugin})"),
+                error => format!("failed: {error}"),
+            };
+
+            return vec![[
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                status,
+                path,
+            ]];
+        }
+    };
+
+    // Every plugin of a shell gets its own line, the way hosts list them
+    if let PluginInfo::Vst2(shell) = info
+        && !shell.shell_plugins.is_empty()
+    {
+        return shell
+            .shell_plugins
+            .iter()
+            .map(|plugin| {
+                [
+                    info.format().to_string(),
+                    plugin.name.clone().unwrap_or_else(|| "-".to_string()),
+                    plugin.vendor.clone().unwrap_or_else(|| "-".to_string()),
+                    format!("shell id {}", plugin.shell_id.unwrap_or_default()),
+                    path.clone(),
+                ]
+            })
+            .collect();
+    }
+
+    let status = match info {
+        PluginInfo::Partial(_) => "partial",
+        _ => "ok",
+    };
+
+    vec![[
+        info.format().to_string(),
+        info.name().unwrap_or("-").to_string(),
+        info.vendor().unwrap_or("-").to_string(),
+        status.to_string(),
+        path,
+    ]]
+}
+
+fn default_cache_path() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+
+    base.unwrap_or_else(std::env::temp_dir)
+        .join("audio-plugin-metadata")
+        .join("scan-cache.bin")
+}
+
+fn run(cli: Cli) -> Result<bool, Box<dyn std::error::Error>> {
+    let cache_path = cli.cache.unwrap_or_else(default_cache_path);
+    let mut printer = Printer::new(cli.format);
+    let mut success = true;
+
+    match cli.command {
+        Command::Scan {
+            dirs,
+            scan,
+            max_depth,
+            follow_links,
+            no_cache,
+        } => {
+            let scanner = Scanner::new(&scan);
+            // Partial results of static scans must not shadow complete ones
+            let use_cache = !no_cache && !scan.static_only;
+            let mut cache = ScanCache::load(&cache_path, false)?;
+            let options = WalkOptions {
+                max_depth,
+                follow_links,
+                ..Default::default()
+            };
+
+            for dir in &dirs {
+                for path in scan_path_with(dir, EXTENSIONS, options.clone()) {
+                    let result = if use_cache {
+                        cache
+                            .get_or_scan(&path, |path| scanner.scan(path))
+                            .map(|info| printer.push(&path, Ok(info)))
+                    } else {
+                        scanner
+                            .scan(&path)
+                            .map(|info| printer.push(&path, Ok(&info)))
+                    };
+
+                    match result {
+                        Ok(printed) => printed?,
+                        Err(error) => printer.push(&path, Err(&error))?,
+                    }
+                }
+            }
+
+            if use_cache {
+                cache.save(&cache_path)?;
+            }
+        }
+        Command::Info {
+            plugin,
+            scan,
+            trace,
+        } => {
+            let scanner = Scanner::new(&scan);
+            let result = match &trace {
+                Some(trace_path) => {
+                    let (result, trace) = scanner.scan_traced(&plugin);
+                    // Written even when the scan failed, that's when it's needed most
+                    let file = std::fs::File::create(trace_path)?;
+                    serde_json::to_writer_pretty(io::BufWriter::new(file), &trace)?;
+                    result
+                }
+                None => scanner.scan(&plugin),
+            };
+
+            match result {
+                Ok(info) => printer.push(&plugin, Ok(&info))?,
+                Err(error) => {
+                    printer.push(&plugin, Err(&error))?;
+                    success = false;
+                }
+            }
+        }
+        Command::Cache { command } => {
+            let mut cache = ScanCache::load(&cache_path, false)?;
+
+            match command {
+                CacheCommand::List => {
+                    let mut entries: Vec<_> = cache.iter().collect();
+                    entries.sort_by_key(|(path, _)| *path);
+
+                    for (path, info) in entries {
+                        printer.push(path, Ok(info))?;
+                    }
+                }
+                CacheCommand::Clear => {
+                    cache.clear();
+                    cache.save(&cache_path)?;
+                }
+                CacheCommand::Rescan { scan } => {
+                    // Partial results of static scans would replace the complete cached ones
+                    if scan.static_only {
+                        Cli::command()
+                            .error(
+                                ErrorKind::ArgumentConflict,
+                                "--static-only can't be used to rescan the cache",
+                            )
+                            .exit();
+                    }
+
+                    let scanner = Scanner::new(&scan);
+                    cache.prune();
+
+                    let mut paths: Vec<PathBuf> =
+                        cache.iter().map(|(path, _)| path.to_path_buf()).collect();
+                    paths.sort();
+
+                    for path in paths {
+                        match scanner.scan(&path) {
+                            Ok(info) => {
+                                printer.push(&path, Ok(&info))?;
+                                cache.insert(&path, info)?;
+                            }
+                            Err(error) => {
+                                printer.push(&path, Err(&error))?;
+                                cache.remove(&path);
+                            }
+                        }
+                    }
+
+                    cache.save(&cache_path)?;
+                }
+            }
+        }
+    }
+
+    printer.finish()?;
+    Ok(success)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
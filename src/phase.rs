@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// The step of a scan that is currently running plugin code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ScanPhase {
+    /// Loading the binary and running its static initialisers
+    Load,
+    /// Calling the entry point (`VSTPluginMain`, `GetPluginFactory`)
+    Entry,
+    /// Opening the instance (`effOpen`)
+    Open,
+    /// Reading the plugin's metadata
+    Query,
+}
+
+impl fmt::Display for ScanPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScanPhase::Load => "load",
+            ScanPhase::Entry => "entry",
+            ScanPhase::Open => "open",
+            ScanPhase::Query => "query",
+        };
+
+        f.write_str(name)
+    }
+}
+
+type PhaseObserver = Box<dyn Fn(ScanPhase)>;
+
+thread_local! {
+    static OBSERVER: RefCell<Option<PhaseObserver>> = const { RefCell::new(None) };
+}
+
+/// Installs a callback that is told about every phase change of scans running on this thread
+pub fn set_phase_observer(observer: Option<PhaseObserver>) {
+    OBSERVER.with(|current| *current.borrow_mut() = observer);
+}
+
+pub(crate) fn enter(phase: ScanPhase) {
+    debug!("Entering scan phase {phase}");
+
+    OBSERVER.with(|current| {
+        if let Some(observer) = current.borrow().as_ref() {
+            observer(phase);
+        }
+    });
+}
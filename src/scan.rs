@@ -1,17 +1,220 @@
 use std::path::{Path, PathBuf};
 
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
-pub fn scan_path<'a>(root: &Path, extensions: &'a [&str]) -> impl Iterator<Item = PathBuf> + 'a {
-    WalkDir::new(root)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file())
-        .filter_map(move |entry| {
-            let ext = entry.path().extension()?.to_str()?;
-            extensions
+use crate::bundle::is_bundle;
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub max_depth: Option<usize>,
+    pub follow_links: bool,
+    pub skip_hidden: bool,
+    /// Directories to leave out. An entry matches either the full path or just the directory name.
+    pub exclude: Vec<PathBuf>,
+}
+
+impl WalkOptions {
+    fn skips(&self, entry: &DirEntry) -> bool {
+        if entry.depth() == 0 {
+            return false;
+        }
+
+        if self.skip_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+            return true;
+        }
+
+        entry.file_type().is_dir()
+            && self
+                .exclude
                 .iter()
-                .any(|wanted| wanted.eq_ignore_ascii_case(ext))
-                .then(|| entry.into_path())
-        })
+                .any(|excluded| entry.path() == excluded || entry.file_name() == excluded)
+    }
+}
+
+pub fn scan_path<'a>(root: &Path, extensions: &'a [&str]) -> impl Iterator<Item = PathBuf> + 'a {
+    scan_path_with(root, extensions, WalkOptions::default())
+}
+
+/// Walks `root` and yields plugin files and plugin bundles. Bundles are yielded as a single path
+/// and never descended into.
+pub fn scan_path_with<'a>(
+    root: &Path,
+    extensions: &'a [&str],
+    options: WalkOptions,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let mut walker = WalkDir::new(root).follow_links(options.follow_links);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    let mut entries = walker.into_iter();
+
+    std::iter::from_fn(move || {
+        loop {
+            let entry = match entries.next()? {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            if options.skips(&entry) {
+                if entry.file_type().is_dir() {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+
+            if entry.file_type().is_dir() {
+                if is_bundle(entry.path()) {
+                    entries.skip_current_dir();
+
+                    if has_extension(entry.path(), extensions) {
+                        return Some(entry.into_path());
+                    }
+                }
+                continue;
+            }
+
+            if entry.file_type().is_file() && has_extension(entry.path(), extensions) {
+                return Some(entry.into_path());
+            }
+        }
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+
+    extensions
+        .iter()
+        .any(|wanted| wanted.eq_ignore_ascii_case(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    const EXTENSIONS: &[&str] = &["vst3", "dll", "so"];
+
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[&str]) -> Tree {
+            let root = std::env::temp_dir().join(format!("apm-scan-{name}-{}", process::id()));
+            for file in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, b"").unwrap();
+            }
+            Tree(root)
+        }
+
+        fn walk(&self, options: WalkOptions) -> Vec<String> {
+            let mut found: Vec<String> = scan_path_with(&self.0, EXTENSIONS, options)
+                .map(|path| {
+                    let path = path.strip_prefix(&self.0).unwrap();
+                    path.to_string_lossy().replace('\\', "/")
+                })
+                .collect();
+            found.sort();
+            found
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let tree = Tree::new("depth", &["a.dll", "sub/b.dll", "sub/deeper/c.dll"]);
+
+        assert_eq!(
+            tree.walk(WalkOptions::default()),
+            ["a.dll", "sub/b.dll", "sub/deeper/c.dll"]
+        );
+
+        let depth = |max_depth| WalkOptions {
+            max_depth: Some(max_depth),
+            ..Default::default()
+        };
+        assert_eq!(tree.walk(depth(2)), ["a.dll", "sub/b.dll"]);
+        assert_eq!(tree.walk(depth(1)), ["a.dll"]);
+        assert!(tree.walk(depth(0)).is_empty());
+    }
+
+    #[test]
+    fn bundles_are_not_descended_into() {
+        let tree = Tree::new(
+            "bundles",
+            &[
+                "Synth.vst3/Contents/x86_64-win/Synth.vst3",
+                "Synth.vst3/Contents/x86_64-linux/Synth.so",
+                "Synth.vst3/Contents/Resources/moduleinfo.json",
+                "Other.clap/Contents/x86_64-linux/Other.so",
+                "plain/Fx.vst3",
+            ],
+        );
+
+        assert_eq!(
+            tree.walk(WalkOptions::default()),
+            ["Synth.vst3", "plain/Fx.vst3"]
+        );
+    }
+
+    #[test]
+    fn extension_filter() {
+        let tree = Tree::new(
+            "extensions",
+            &[
+                "a.DLL",
+                "b.so",
+                "c.Vst3",
+                "d.clap",
+                "readme.txt",
+                "dll",
+                "e.dll.bak",
+            ],
+        );
+
+        assert_eq!(
+            tree.walk(WalkOptions::default()),
+            ["a.DLL", "b.so", "c.Vst3"]
+        );
+    }
+
+    #[test]
+    fn hidden_and_excluded_directories() {
+        let tree = Tree::new(
+            "skipped",
+            &["a.dll", ".hidden/b.dll", "old/c.dll", "keep/old.dll"],
+        );
+
+        let options = WalkOptions {
+            skip_hidden: true,
+            exclude: vec![PathBuf::from("old")],
+            ..Default::default()
+        };
+        assert_eq!(tree.walk(options), ["a.dll", "keep/old.dll"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_links() {
+        let tree = Tree::new("links", &["a.dll", "target/b.dll"]);
+        std::os::unix::fs::symlink(tree.0.join("target"), tree.0.join("link")).unwrap();
+
+        assert_eq!(tree.walk(WalkOptions::default()), ["a.dll", "target/b.dll"]);
+
+        let options = WalkOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        assert_eq!(tree.walk(options), ["a.dll", "link/b.dll", "target/b.dll"]);
+    }
 }
@@ -0,0 +1,117 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+/// Everything the host and the plugin said to each other during a scan, in order
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct ScanTrace {
+    pub events: Vec<TraceEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum TraceDirection {
+    HostToPlugin,
+    PluginToHost,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub enum TraceEvent {
+    /// A dispatcher call, or a host callback call when the plugin is the caller
+    Vst2 {
+        direction: TraceDirection,
+        opcode: i32,
+        index: i32,
+        value: i64,
+        opt: f32,
+        /// String passed in or returned through `ptr`, for the opcodes that carry one
+        text: Option<String>,
+        result: i64,
+    },
+    /// A `getParameter` call, parameters are read through their own function pointer
+    Vst2GetParameter { index: i32, result: f32 },
+    /// A call of an interface method
+    Vst3 {
+        direction: TraceDirection,
+        interface: String,
+        method: String,
+        index: Option<i32>,
+        result: i32,
+    },
+}
+
+type Observer = dyn Fn(&TraceEvent) + Send + Sync;
+
+/// Collects trace events. Clones share the same trace, so one recorder can be handed to every
+/// callback and thread of a scan.
+#[derive(Clone, Default)]
+pub struct TraceRecorder {
+    events: Arc<Mutex<Vec<TraceEvent>>>,
+    observer: Option<Arc<Observer>>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> TraceRecorder {
+        TraceRecorder::default()
+    }
+
+    /// A recorder that passes every event to `observer` as soon as it happens, instead of
+    /// collecting them
+    pub fn with_observer(observer: impl Fn(&TraceEvent) + Send + Sync + 'static) -> TraceRecorder {
+        TraceRecorder {
+            events: Default::default(),
+            observer: Some(Arc::new(observer)),
+        }
+    }
+
+    pub fn record(&self, event: TraceEvent) {
+        match &self.observer {
+            Some(observer) => observer(&event),
+            None => self
+                .events
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(event),
+        }
+    }
+
+    pub(crate) fn vst3_call(
+        &self,
+        direction: TraceDirection,
+        interface: &str,
+        method: &str,
+        index: Option<i32>,
+        result: i32,
+    ) {
+        self.record(TraceEvent::Vst3 {
+            direction,
+            interface: interface.to_string(),
+            method: method.to_string(),
+            index,
+            result,
+        });
+    }
+
+    /// The events recorded so far
+    pub fn trace(&self) -> ScanTrace {
+        ScanTrace {
+            events: self
+                .events
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        }
+    }
+}
+
+impl fmt::Debug for TraceRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceRecorder")
+            .field("events", &self.events)
+            .field("observer", &self.observer.is_some())
+            .finish()
+    }
+}
@@ -1,10 +1,121 @@
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
-use crate::{vst2::types::Vst2Info, vst3::types::Vst3Info};
+use crate::{
+    arch::{BinaryArch, BinaryInfo, FileVersionInfo},
+    vst2::types::Vst2Info,
+    vst3::types::Vst3Info,
+};
 
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
 pub enum PluginInfo {
     Vst2(Vst2Info),
     Vst3(Vst3Info),
+    /// Result of a static-only scan that couldn't recover the complete format-specific info
+    Partial(PartialInfo),
+}
+
+impl PluginInfo {
+    pub fn format(&self) -> PluginFormat {
+        match self {
+            PluginInfo::Vst2(_) => PluginFormat::Vst2,
+            PluginInfo::Vst3(_) => PluginFormat::Vst3,
+            PluginInfo::Partial(info) => info.format,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            PluginInfo::Vst2(info) => info.name.as_deref(),
+            PluginInfo::Vst3(info) => info.classes.audio_module_name(),
+            PluginInfo::Partial(info) => info.name.as_deref(),
+        }
+    }
+
+    pub fn vendor(&self) -> Option<&str> {
+        match self {
+            PluginInfo::Vst2(info) => info.vendor.as_deref(),
+            PluginInfo::Vst3(info) => Some(info.factory_info.vendor.as_str()),
+            PluginInfo::Partial(info) => info.vendor.as_deref(),
+        }
+        .filter(|vendor| !vendor.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum PluginFormat {
+    Vst2,
+    Vst3,
+}
+
+impl std::fmt::Display for PluginFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginFormat::Vst2 => f.write_str("VST2"),
+            PluginFormat::Vst3 => f.write_str("VST3"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct PartialInfo {
+    pub format: PluginFormat,
+    pub binary: Option<String>,
+    pub arch: Option<BinaryArch>,
+    /// Format and every architecture slice of the binary
+    pub binary_info: Option<BinaryInfo>,
+    pub name: Option<String>,
+    pub vendor: Option<String>,
+    pub version: Option<String>,
+    pub identifier: Option<String>,
+    pub copyright: Option<String>,
+    pub file_version_info: Option<FileVersionInfo>,
+    /// Names of the fields that weren't available without running plugin code
+    pub unavailable: Vec<String>,
+}
+
+impl PartialInfo {
+    pub fn new(format: PluginFormat) -> PartialInfo {
+        PartialInfo {
+            format,
+            binary: None,
+            arch: None,
+            binary_info: None,
+            name: None,
+            vendor: None,
+            version: None,
+            identifier: None,
+            copyright: None,
+            file_version_info: None,
+            unavailable: vec![],
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> PartialInfo {
+        let optional = [
+            ("binary", self.binary.is_none()),
+            ("arch", self.arch.is_none()),
+            ("name", self.name.is_none()),
+            ("vendor", self.vendor.is_none()),
+            ("version", self.version.is_none()),
+            ("identifier", self.identifier.is_none()),
+            ("copyright", self.copyright.is_none()),
+        ];
+
+        // These only come from querying the plugin itself
+        let queried: &[&str] = match self.format {
+            PluginFormat::Vst2 => &["unique_id", "category"],
+            PluginFormat::Vst3 => &["factory_info", "classes"],
+        };
+
+        self.unavailable = optional
+            .iter()
+            .filter(|(_, missing)| *missing)
+            .map(|(field, _)| field)
+            .chain(queried)
+            .map(|field| field.to_string())
+            .collect();
+
+        self
+    }
 }
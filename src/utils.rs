@@ -1,9 +1,12 @@
-use std::ffi::CStr;
-
+/// Reads up to the first NUL, or the whole slice when plugins leave out the terminator
 pub fn i8_to_string(data: &[i8]) -> String {
-    unsafe { CStr::from_ptr(data.as_ptr()) }
-        .to_string_lossy()
-        .into()
+    let bytes: Vec<u8> = data
+        .iter()
+        .map(|&byte| byte as u8)
+        .take_while(|&byte| byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
 }
 
 pub fn i16_to_string(data: &[i16]) -> String {
@@ -15,3 +18,30 @@ pub fn i16_to_string(data: &[i16]) -> String {
 
     String::from_utf16_lossy(&vector)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn i8_to_string_stops_at_nul_or_end() {
+        let text = |bytes: &[u8]| bytes.iter().map(|&byte| byte as i8).collect::<Vec<_>>();
+
+        assert_eq!(i8_to_string(&text(b"Gain\0dB\0")), "Gain");
+        assert_eq!(i8_to_string(&text(b"Unterminated")), "Unterminated");
+        assert_eq!(i8_to_string(&text(b"\0")), "");
+        assert_eq!(i8_to_string(&[]), "");
+    }
+
+    #[test]
+    fn i16_to_string_stops_at_nul_or_end() {
+        let text = |text: &str| {
+            text.encode_utf16()
+                .map(|unit| unit as i16)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(i16_to_string(&text("Synth\0x")), "Synth");
+        assert_eq!(i16_to_string(&text("Ünterminated")), "Ünterminated");
+    }
+}
@@ -1,153 +1,339 @@
-use std::{
-    ffi::CStr,
-    os::raw::{c_char, c_void},
-    panic::{AssertUnwindSafe, catch_unwind},
-    path::Path,
-    ptr::NonNull,
-};
+use std::{ffi::CString, mem::MaybeUninit, os::raw::c_void, path::Path};
 
-use libloading::Symbol;
+use host::{HostContext, HostState};
 use tracing::{debug, error};
-use types::{Vst2Category, Vst2Info, Vst2IntPtr, Vst2Main};
+use types::{
+    CanDoAnswer, RawParameterProperties, Vst2CanDo, Vst2Capabilities, Vst2Capability, Vst2Category,
+    Vst2Flags, Vst2Info, Vst2IntPtr, Vst2Main, Vst2Parameter, Vst2ParameterFlags,
+    Vst2ParameterProperties,
+};
 use vst2_sys::{AEffect, effect_opcodes as opcode};
 
-use crate::lib_loader::load_dll;
+use crate::{
+    arch::read_pe_version_info,
+    config::ScanConfig,
+    error::ScanError,
+    lib_loader::load_dll,
+    phase::{self, ScanPhase},
+    trace::TraceRecorder,
+    utils::i8_to_string,
+};
 
+mod host;
+pub mod opcodes;
 pub mod types;
 
-extern "C" fn dummy_host_callback(
-    _effect: *mut AEffect,
-    opcode: i32,
-    _index: i32,
-    _value: Vst2IntPtr,
-    ptr: *mut c_void,
-    _opt: f32,
-) -> Vst2IntPtr {
-    match opcode {
-        1 => 2100, // audioMasterVersion
-        33 => {
-            if ptr.is_null() {
-                return 0;
-            }
+// Older plugins only export `main`
+pub(crate) const ENTRY_POINTS: &[&str] = &["VSTPluginMain", "main"];
 
-            // audioMasterGetVendorString
-            let s = b"MyHost\0";
-            unsafe {
-                std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, s.len().min(64));
-            }
-            1
-        }
-        34 => {
-            if ptr.is_null() {
-                return 0;
-            }
+pub fn scan_vst2(
+    path: &Path,
+    config: &ScanConfig,
+    trace: Option<&TraceRecorder>,
+) -> Result<Vst2Info, ScanError> {
+    phase::enter(ScanPhase::Load);
+    let lib = load_dll(path, &config.search_paths)?;
 
-            // audioMasterGetProductString
-            let s = b"MyHostProduct\0";
-            unsafe {
-                std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, s.len().min(64));
-            }
-            1
-        }
-        35 => 1000, // audioMasterGetVendorVersion
-        vst2_sys::host_opcodes::CAN_DO => {
-            if ptr.is_null() {
-                return 0;
-            }
+    phase::enter(ScanPhase::Entry);
+    let (entry, vst_main) = ENTRY_POINTS
+        .iter()
+        .find_map(|&name| {
+            let symbol = unsafe { lib.get::<Vst2Main>(name.as_bytes()) }.ok()?;
+            Some((name, *symbol))
+        })
+        .ok_or_else(|| ScanError::MissingEntryPoint {
+            symbols: ENTRY_POINTS.iter().map(|name| name.to_string()).collect(),
+        })?;
 
-            let result = catch_unwind(AssertUnwindSafe(|| {
-                let cstr = unsafe { CStr::from_ptr(ptr as *const c_char) };
-                debug!("Can do {:?}", cstr);
-
-                if let Ok(text) = cstr.to_str() {
-                    match text {
-                        "sendVstTimeInfo" => 0,
-                        _ => 0,
-                    }
-                } else {
-                    0
-                }
-            }));
+    let setup = HostState::new(&config.host, path, trace.cloned());
+    let mut info = scan_effect(vst_main, entry, &setup, None)?;
+    info.file_version_info = read_pe_version_info(path).ok().flatten();
 
-            match result {
-                Ok(v) => v,
-                Err(_) => {
-                    error!("CAN_DO panic: plugin passed invalid pointer or string");
-                    0
-                }
-            }
-        }
-        _ => {
-            debug!("Unhandled host opcode: {}", opcode);
-            0
-        }
-    }
+    Ok(info)
 }
 
-pub fn scan_vst2(path: &Path) -> Result<Vst2Info, Box<dyn std::error::Error>> {
-    let lib = load_dll(path)?;
-
-    let vst_main: Symbol<Vst2Main> =
-        unsafe { lib.get(b"VSTPluginMain").or_else(|_| lib.get(b"main"))? };
+fn scan_effect(
+    vst_main: Vst2Main,
+    entry: &str,
+    setup: &HostState,
+    shell_id: Option<u32>,
+) -> Result<Vst2Info, ScanError> {
+    phase::enter(ScanPhase::Entry);
+    let context = HostContext::new(setup.for_shell(shell_id));
+    let instance =
+        host::instantiate(vst_main, context).ok_or_else(|| ScanError::EntryReturnedNull {
+            entry: entry.to_string(),
+        })?;
+    let eff = instance.effect();
 
-    let effect = unsafe { vst_main(dummy_host_callback) };
-    let effect = NonNull::new(effect).ok_or("effect is null")?;
-    let eff = unsafe { effect.as_ref() };
+    phase::enter(ScanPhase::Open);
+    host::call_dispatcher(eff, opcode::OPEN, 0, 0, std::ptr::null_mut(), 0.0);
 
-    ((eff.dispatcher)(
-        eff as *const _ as *mut AEffect,
-        opcode::OPEN,
+    // Hosts pass the processing setup right after opening, some plugins rely on it
+    let (sample_rate, block_size) = {
+        let state = instance.context().state();
+        (state.sample_rate(), state.block_size())
+    };
+    host::call_dispatcher(
+        eff,
+        opcodes::effect::SET_SAMPLE_RATE,
         0,
         0,
         std::ptr::null_mut(),
-        0.0,
-    ));
+        sample_rate as f32,
+    );
+    dispatch(
+        eff,
+        opcodes::effect::SET_BLOCK_SIZE,
+        0,
+        block_size as Vst2IntPtr,
+    );
 
+    phase::enter(ScanPhase::Query);
     let name = get_string(eff, opcode::GET_EFFECT_NAME)
         .or_else(|| get_string(eff, opcode::GET_PRODUCT_STRING));
     let category_raw = get_num(eff, opcode::GET_PLUG_CATEGORY) as i32;
+    let category = Vst2Category::from_num(category_raw);
 
-    let info = Vst2Info {
+    // Only the shell itself lists sub-plugins, they must not be asked again
+    let shell_plugins = match (&category, shell_id) {
+        (Vst2Category::Shell, None) => get_shell_plugins(eff),
+        _ => vec![],
+    };
+
+    let mut info = Vst2Info {
         name,
         vendor: get_string(eff, opcode::GET_VENDOR_STRING),
         version: eff.version as u32,
-        category: Vst2Category::from_num(category_raw),
+        category,
         category_raw,
         unique_id: eff.unique_id as u32,
+        // Read after `effOpen`, plugins may change their I/O while opening
+        num_inputs: eff.num_inputs,
+        num_outputs: eff.num_outputs,
+        initial_delay: eff.initial_delay,
+        flags: Vst2Flags::from_raw(eff.flags),
+        flags_raw: eff.flags,
+        capabilities: get_capabilities(eff),
+        shell_id,
+        shell_plugins: vec![],
+        parameters: get_parameters(eff),
+        programs: get_programs(eff),
+        file_version_info: None,
     };
 
-    ((eff.dispatcher)(
-        eff as *const _ as *mut AEffect,
-        opcode::CLOSE,
-        0,
+    host::call_dispatcher(eff, opcode::CLOSE, 0, 0, std::ptr::null_mut(), 0.0);
+
+    debug!(
+        "Host opcodes called by the plugin: {:?}",
+        instance.context().state().requests()
+    );
+    // A shell may hand out the same address for the next sub-plugin
+    drop(instance);
+
+    for (id, shell_name) in shell_plugins {
+        debug!("Going to scan shell plugin {id} {shell_name:?}");
+
+        match scan_effect(vst_main, entry, setup, Some(id)) {
+            Ok(mut plugin) => {
+                if plugin.name.is_none() {
+                    plugin.name = shell_name;
+                }
+                info.shell_plugins.push(plugin);
+            }
+            Err(err) => error!("Cannot scan shell plugin {id}: {err}"),
+        }
+    }
+
+    Ok(info)
+}
+
+fn get_shell_plugins(eff: &AEffect) -> Vec<(u32, Option<String>)> {
+    let mut plugins = vec![];
+
+    loop {
+        let mut buffer = [0i8; 64];
+        let id = host::call_dispatcher(
+            eff,
+            opcodes::effect::SHELL_GET_NEXT_PLUGIN,
+            0,
+            0,
+            buffer.as_mut_ptr() as *mut c_void,
+            0.0,
+        );
+
+        // Some shells keep returning the last ID instead of 0
+        if id == 0 || plugins.iter().any(|(known, _)| *known == id as u32) {
+            break;
+        }
+
+        let name = i8_to_string(&buffer);
+        plugins.push((id as u32, (!name.is_empty()).then_some(name)));
+    }
+
+    plugins
+}
+
+fn get_capabilities(eff: &AEffect) -> Vst2Capabilities {
+    let entries = Vst2CanDo::ALL
+        .into_iter()
+        .map(|can_do| {
+            let text = CString::new(can_do.as_str()).expect("canDo strings have no NUL bytes");
+            let result = host::call_dispatcher(
+                eff,
+                opcodes::effect::CAN_DO,
+                0,
+                0,
+                text.as_ptr() as *mut c_void,
+                0.0,
+            );
+
+            Vst2Capability {
+                can_do,
+                answer: CanDoAnswer::from_num(result),
+            }
+        })
+        .collect();
+
+    Vst2Capabilities { entries }
+}
+
+fn get_programs(eff: &AEffect) -> Vec<String> {
+    let mut programs = Vec::with_capacity(eff.num_programs.max(0) as usize);
+
+    for index in 0..eff.num_programs {
+        let mut buffer = [0i8; 256];
+        let result = host::call_dispatcher(
+            eff,
+            opcodes::effect::GET_PROGRAM_NAME_INDEXED,
+            index,
+            -1,
+            buffer.as_mut_ptr() as *mut c_void,
+            0.0,
+        );
+
+        if result == 0 {
+            debug!("effGetProgramNameIndexed isn't supported, switching through the programs");
+            return get_programs_by_switching(eff);
+        }
+
+        buffer[buffer.len() - 1] = 0;
+        programs.push(i8_to_string(&buffer).trim().to_string());
+    }
+
+    programs
+}
+
+fn get_programs_by_switching(eff: &AEffect) -> Vec<String> {
+    let current = dispatch(eff, opcodes::effect::GET_PROGRAM, 0, 0);
+
+    let programs = (0..eff.num_programs)
+        .map(|index| {
+            dispatch(eff, opcodes::effect::SET_PROGRAM, 0, index as Vst2IntPtr);
+            get_indexed_string(eff, opcodes::effect::GET_PROGRAM_NAME, 0)
+        })
+        .collect();
+
+    dispatch(eff, opcodes::effect::SET_PROGRAM, 0, current);
+    programs
+}
+
+fn get_parameters(eff: &AEffect) -> Vec<Vst2Parameter> {
+    (0..eff.num_params)
+        .map(|index| Vst2Parameter {
+            index,
+            name: get_indexed_string(eff, opcodes::effect::GET_PARAM_NAME, index),
+            label: get_indexed_string(eff, opcodes::effect::GET_PARAM_LABEL, index),
+            display: get_indexed_string(eff, opcodes::effect::GET_PARAM_DISPLAY, index),
+            default_value: host::call_get_parameter(eff, index),
+            automatable: dispatch(eff, opcodes::effect::CAN_BE_AUTOMATED, index, 0) == 1,
+            properties: get_parameter_properties(eff, index),
+        })
+        .collect()
+}
+
+fn get_parameter_properties(eff: &AEffect, index: i32) -> Option<Vst2ParameterProperties> {
+    let mut raw = MaybeUninit::<RawParameterProperties>::zeroed();
+
+    let result = host::call_dispatcher(
+        eff,
+        opcodes::effect::GET_PARAMETER_PROPERTIES,
+        index,
         0,
-        std::ptr::null_mut(),
+        raw.as_mut_ptr() as *mut c_void,
         0.0,
-    ));
+    );
 
-    Ok(info)
+    if result == 0 {
+        return None;
+    }
+
+    // All zeroes is a valid value for every field
+    let raw = unsafe { raw.assume_init() };
+
+    let all_flags = [
+        Vst2ParameterFlags::IsSwitch,
+        Vst2ParameterFlags::UsesIntegerMinMax,
+        Vst2ParameterFlags::UsesFloatStep,
+        Vst2ParameterFlags::UsesIntStep,
+        Vst2ParameterFlags::SupportsDisplayIndex,
+        Vst2ParameterFlags::SupportsDisplayCategory,
+        Vst2ParameterFlags::CanRamp,
+    ];
+
+    let flags = all_flags
+        .into_iter()
+        .enumerate()
+        .filter(|(bit, _)| raw.flags & (1 << bit) != 0)
+        .map(|(_, flag)| flag)
+        .collect();
+
+    Some(Vst2ParameterProperties {
+        label: i8_to_string(&raw.label),
+        short_label: i8_to_string(&raw.short_label),
+        category_label: i8_to_string(&raw.category_label),
+        flags,
+        step_float: raw.step_float,
+        small_step_float: raw.small_step_float,
+        large_step_float: raw.large_step_float,
+        min_integer: raw.min_integer,
+        max_integer: raw.max_integer,
+        step_integer: raw.step_integer,
+        large_step_integer: raw.large_step_integer,
+    })
 }
 
-fn get_string(eff: &AEffect, opcode: i32) -> Option<String> {
-    let mut buffer = [0i8; 64];
-    let dispatcher = eff.dispatcher;
+// Parameter strings are specified to be 8 chars, but hardly any plugin keeps to that
+fn get_indexed_string(eff: &AEffect, opcode: i32, index: i32) -> String {
+    let mut buffer = [0i8; 256];
 
-    let result = dispatcher(
-        eff as *const _ as *mut AEffect,
+    host::call_dispatcher(
+        eff,
         opcode,
-        0,
+        index,
         0,
         buffer.as_mut_ptr() as *mut c_void,
         0.0,
     );
 
+    // Plugins that overrun even this leave no terminator
+    buffer[buffer.len() - 1] = 0;
+    i8_to_string(&buffer).trim().to_string()
+}
+
+fn dispatch(eff: &AEffect, opcode: i32, index: i32, value: Vst2IntPtr) -> Vst2IntPtr {
+    host::call_dispatcher(eff, opcode, index, value, std::ptr::null_mut(), 0.0)
+}
+
+fn get_string(eff: &AEffect, opcode: i32) -> Option<String> {
+    let mut buffer = [0i8; 64];
+    let result = host::call_dispatcher(eff, opcode, 0, 0, buffer.as_mut_ptr() as *mut c_void, 0.0);
+
     if result == 0 {
         return None;
     }
 
-    let string = unsafe { CStr::from_ptr(buffer.as_ptr()) }
-        .to_string_lossy()
-        .into_owned();
+    let string = i8_to_string(&buffer);
 
     if string.is_empty() {
         return None;
@@ -157,14 +343,5 @@ fn get_string(eff: &AEffect, opcode: i32) -> Option<String> {
 }
 
 fn get_num(eff: &AEffect, opcode: i32) -> Vst2IntPtr {
-    let dispatcher = eff.dispatcher;
-
-    dispatcher(
-        eff as *const _ as *mut AEffect,
-        opcode,
-        0,
-        0,
-        std::ptr::null_mut(),
-        0.0,
-    )
+    host::call_dispatcher(eff, opcode, 0, 0, std::ptr::null_mut(), 0.0)
 }
@@ -0,0 +1,439 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_void},
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::Path,
+    ptr::NonNull,
+    sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+};
+
+use tracing::{debug, error};
+use vst2_sys::AEffect;
+
+use super::{
+    opcodes::host as opcode,
+    types::{RawTimeInfo, Vst2IntPtr, Vst2Main},
+};
+use crate::{
+    host::HostIdentity,
+    trace::{TraceDirection, TraceEvent, TraceRecorder},
+};
+
+// kVstMaxVendorStrLen and kVstMaxProductStrLen, including the terminator
+const MAX_STRING_LEN: usize = 64;
+
+const SAMPLE_RATE: f64 = 44100.0;
+const BLOCK_SIZE: i32 = 512;
+const TEMPO: f64 = 120.0;
+
+// VstProcessLevels::kVstProcessLevelUser, called from the user thread
+const PROCESS_LEVEL_USER: Vst2IntPtr = 1;
+// VstAutomationStates::kVstAutomationOff
+const AUTOMATION_OFF: Vst2IntPtr = 1;
+// VstHostLanguage::kVstLangEnglish
+const LANGUAGE_ENGLISH: Vst2IntPtr = 1;
+
+// VstTimeInfoFlags
+const TIME_PPQ_POS_VALID: i32 = 1 << 9;
+const TIME_TEMPO_VALID: i32 = 1 << 10;
+const TIME_BARS_VALID: i32 = 1 << 11;
+const TIME_SIG_VALID: i32 = 1 << 13;
+
+/// What the host callback answers with while a plugin is scanned
+#[derive(Clone)]
+pub(crate) struct HostState {
+    identity: HostIdentity,
+    // Answer to `audioMasterCurrentId`, tells a shell which sub-plugin to instantiate
+    shell_id: i32,
+    sample_rate: f64,
+    block_size: i32,
+    // Answer to `audioMasterGetDirectory`, the folder of the plugin
+    directory: CString,
+    // Plugins keep the pointer returned by `audioMasterGetTime`, so it needs a stable address
+    time_info: Box<RawTimeInfo>,
+    // Host opcodes the plugin called, in order
+    requests: Vec<i32>,
+    trace: Option<TraceRecorder>,
+}
+
+impl HostState {
+    pub(crate) fn new(
+        identity: &HostIdentity,
+        plugin: &Path,
+        trace: Option<TraceRecorder>,
+    ) -> HostState {
+        let directory = plugin
+            .parent()
+            .and_then(|dir| CString::new(dir.to_string_lossy().into_owned()).ok())
+            .unwrap_or_default();
+
+        HostState {
+            identity: identity.clone(),
+            shell_id: 0,
+            sample_rate: SAMPLE_RATE,
+            block_size: BLOCK_SIZE,
+            directory,
+            time_info: Box::new(RawTimeInfo {
+                sample_rate: SAMPLE_RATE,
+                tempo: TEMPO,
+                time_sig_numerator: 4,
+                time_sig_denominator: 4,
+                flags: TIME_PPQ_POS_VALID | TIME_TEMPO_VALID | TIME_BARS_VALID | TIME_SIG_VALID,
+                ..Default::default()
+            }),
+            requests: vec![],
+            trace,
+        }
+    }
+
+    /// A fresh state for instantiating the shell sub-plugin `shell_id`
+    pub(crate) fn for_shell(&self, shell_id: Option<u32>) -> HostState {
+        HostState {
+            shell_id: shell_id.unwrap_or(0) as i32,
+            requests: vec![],
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    pub(crate) fn block_size(&self) -> i32 {
+        self.block_size
+    }
+
+    pub(crate) fn requests(&self) -> &[i32] {
+        &self.requests
+    }
+}
+
+impl Default for HostState {
+    fn default() -> Self {
+        HostState::new(&HostIdentity::default(), Path::new(""), None)
+    }
+}
+
+/// The host side of one plugin instance
+pub(crate) struct HostContext {
+    state: Mutex<HostState>,
+}
+
+impl HostContext {
+    pub(crate) fn new(state: HostState) -> Arc<HostContext> {
+        Arc::new(HostContext {
+            state: Mutex::new(state),
+        })
+    }
+
+    pub(crate) fn state(&self) -> MutexGuard<'_, HostState> {
+        // The state stays consistent even if a panic happened while it was locked
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A plugin instance whose callbacks are answered by its own `HostContext`. Dropping it
+/// unregisters the context, the effect itself must be closed before.
+pub(crate) struct Instance {
+    effect: NonNull<AEffect>,
+    context: Arc<HostContext>,
+}
+
+impl Instance {
+    pub(crate) fn effect(&self) -> &AEffect {
+        unsafe { self.effect.as_ref() }
+    }
+
+    pub(crate) fn context(&self) -> &HostContext {
+        &self.context
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        contexts().remove(&(self.effect.as_ptr() as usize));
+    }
+}
+
+// Contexts of all live instances, keyed by their `AEffect` pointer
+static CONTEXTS: LazyLock<Mutex<HashMap<usize, Arc<HostContext>>>> =
+    LazyLock::new(Default::default);
+
+// Answers callbacks that can't be attributed to any instance
+static FALLBACK: LazyLock<Arc<HostContext>> =
+    LazyLock::new(|| HostContext::new(HostState::default()));
+
+thread_local! {
+    // Contexts of the instances being created on this thread. Until the entry point returns, the
+    // plugin calls back with a null or not yet known `AEffect` pointer.
+    static PENDING: RefCell<Vec<Arc<HostContext>>> = const { RefCell::new(vec![]) };
+}
+
+fn contexts() -> MutexGuard<'static, HashMap<usize, Arc<HostContext>>> {
+    CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Calls the entry point with `context` answering all callbacks of the new instance
+pub(crate) fn instantiate(vst_main: Vst2Main, context: Arc<HostContext>) -> Option<Instance> {
+    PENDING.with(|pending| pending.borrow_mut().push(context.clone()));
+    let effect = unsafe { vst_main(host_callback) };
+    PENDING.with(|pending| pending.borrow_mut().pop());
+
+    let effect = NonNull::new(effect)?;
+    contexts().insert(effect.as_ptr() as usize, context.clone());
+
+    Some(Instance { effect, context })
+}
+
+fn find_context(effect: *mut AEffect) -> Arc<HostContext> {
+    if !effect.is_null()
+        && let Some(context) = contexts().get(&(effect as usize))
+    {
+        return context.clone();
+    }
+
+    PENDING
+        .with(|pending| pending.borrow().last().cloned())
+        .unwrap_or_else(|| FALLBACK.clone())
+}
+
+extern "C" fn host_callback(
+    effect: *mut AEffect,
+    opcode: i32,
+    index: i32,
+    value: Vst2IntPtr,
+    ptr: *mut c_void,
+    opt: f32,
+) -> Vst2IntPtr {
+    let context = find_context(effect);
+    let mut state = context.state();
+    state.requests.push(opcode);
+
+    let text = match (&state.trace, host_string_arg(opcode)) {
+        (Some(_), Some(StringArg::In)) => unsafe { read_string(ptr, None) },
+        _ => None,
+    };
+    let result = answer(&mut state, opcode, index, value, ptr);
+
+    if let Some(trace) = &state.trace {
+        let text = match host_string_arg(opcode) {
+            Some(StringArg::Out(len)) if result != 0 => unsafe { read_string(ptr, Some(len)) },
+            _ => text,
+        };
+
+        trace.record(TraceEvent::Vst2 {
+            direction: TraceDirection::PluginToHost,
+            opcode,
+            index,
+            value: value as i64,
+            opt,
+            text,
+            result: result as i64,
+        });
+    }
+
+    result
+}
+
+/// Calls the dispatcher of `eff`, recording the call if its instance is traced
+pub(crate) fn call_dispatcher(
+    eff: &AEffect,
+    opcode: i32,
+    index: i32,
+    value: Vst2IntPtr,
+    ptr: *mut c_void,
+    opt: f32,
+) -> Vst2IntPtr {
+    let effect = eff as *const _ as *mut AEffect;
+    let result = (eff.dispatcher)(effect, opcode, index, value, ptr, opt);
+
+    if let Some(trace) = instance_trace(eff) {
+        let text = match effect_string_arg(opcode) {
+            Some(StringArg::In) => unsafe { read_string(ptr, None) },
+            Some(StringArg::Out(len)) => unsafe { read_string(ptr, Some(len)) },
+            None => None,
+        };
+
+        trace.record(TraceEvent::Vst2 {
+            direction: TraceDirection::HostToPlugin,
+            opcode,
+            index,
+            value: value as i64,
+            opt,
+            text,
+            result: result as i64,
+        });
+    }
+
+    result
+}
+
+/// Calls `getParameter` of `eff`, recording the call if its instance is traced
+pub(crate) fn call_get_parameter(eff: &AEffect, index: i32) -> f32 {
+    let result = (eff.get_parameter)(eff as *const _ as *mut AEffect, index);
+
+    if let Some(trace) = instance_trace(eff) {
+        trace.record(TraceEvent::Vst2GetParameter { index, result });
+    }
+
+    result
+}
+
+fn instance_trace(eff: &AEffect) -> Option<TraceRecorder> {
+    contexts()
+        .get(&(eff as *const _ as usize))
+        .and_then(|context| context.state().trace.clone())
+}
+
+// How an opcode uses `ptr` for a string, with the size of the buffer for returned ones
+enum StringArg {
+    In,
+    Out(usize),
+}
+
+fn effect_string_arg(opcode: i32) -> Option<StringArg> {
+    use super::opcodes::effect;
+    use vst2_sys::effect_opcodes as sys;
+
+    match opcode {
+        effect::CAN_DO => Some(StringArg::In),
+        sys::GET_EFFECT_NAME
+        | sys::GET_VENDOR_STRING
+        | sys::GET_PRODUCT_STRING
+        | effect::SHELL_GET_NEXT_PLUGIN => Some(StringArg::Out(64)),
+        effect::GET_PROGRAM_NAME
+        | effect::GET_PROGRAM_NAME_INDEXED
+        | effect::GET_PARAM_NAME
+        | effect::GET_PARAM_LABEL
+        | effect::GET_PARAM_DISPLAY => Some(StringArg::Out(256)),
+        _ => None,
+    }
+}
+
+fn host_string_arg(opcode: i32) -> Option<StringArg> {
+    match opcode {
+        opcode::CAN_DO => Some(StringArg::In),
+        opcode::GET_VENDOR_STRING | opcode::GET_PRODUCT_STRING => {
+            Some(StringArg::Out(MAX_STRING_LEN))
+        }
+        _ => None,
+    }
+}
+
+// Returned strings are read no further than the buffer that was passed for them
+unsafe fn read_string(ptr: *mut c_void, buffer_len: Option<usize>) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    let bytes = match buffer_len {
+        Some(len) => {
+            let buffer = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+            let end = buffer.iter().position(|&byte| byte == 0).unwrap_or(len);
+            &buffer[..end]
+        }
+        None => unsafe { CStr::from_ptr(ptr as *const c_char) }.to_bytes(),
+    };
+
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn answer(
+    state: &mut HostState,
+    opcode: i32,
+    index: i32,
+    value: Vst2IntPtr,
+    ptr: *mut c_void,
+) -> Vst2IntPtr {
+    match opcode {
+        opcode::VERSION => state.identity.vst_version as Vst2IntPtr,
+        opcode::CURRENT_ID => state.shell_id as Vst2IntPtr,
+        opcode::GET_VENDOR_STRING => write_string(&state.identity.vendor, ptr),
+        opcode::GET_PRODUCT_STRING => write_string(&state.identity.product, ptr),
+        opcode::GET_VENDOR_VERSION => state.identity.version as Vst2IntPtr,
+        opcode::CAN_DO => {
+            if ptr.is_null() {
+                return 0;
+            }
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                let cstr = unsafe { CStr::from_ptr(ptr as *const c_char) };
+                debug!("Can do {:?}", cstr);
+
+                let supported = cstr
+                    .to_str()
+                    .is_ok_and(|text| state.identity.supports(text));
+
+                // 1 is yes, -1 no and 0 "don't know"
+                if supported { 1 } else { 0 }
+            }));
+
+            match result {
+                Ok(v) => v,
+                Err(_) => {
+                    error!("CAN_DO panic: plugin passed invalid pointer or string");
+                    0
+                }
+            }
+        }
+
+        opcode::GET_TIME => {
+            // `value` holds the VstTimeInfoFlags the plugin asks for, everything that's valid is
+            // always filled in
+            debug!("Time info requested with flags {value:#x}");
+            state.time_info.sample_rate = state.sample_rate;
+            &mut *state.time_info as *mut RawTimeInfo as Vst2IntPtr
+        }
+        opcode::TEMPO_AT => (TEMPO * 10000.0) as Vst2IntPtr,
+        opcode::GET_SAMPLE_RATE => state.sample_rate as Vst2IntPtr,
+        opcode::GET_BLOCK_SIZE => state.block_size as Vst2IntPtr,
+        opcode::GET_INPUT_LATENCY | opcode::GET_OUTPUT_LATENCY => 0,
+        opcode::GET_CURRENT_PROCESS_LEVEL => PROCESS_LEVEL_USER,
+        opcode::GET_AUTOMATION_STATE => AUTOMATION_OFF,
+        opcode::GET_LANGUAGE => LANGUAGE_ENGLISH,
+        opcode::GET_DIRECTORY => state.directory.as_ptr() as Vst2IntPtr,
+        // Always replace, a scanner never processes audio
+        opcode::WILL_REPLACE_OR_ACCUMULATE => 1,
+
+        // Notifications the host accepts without acting on them
+        opcode::AUTOMATE | opcode::IDLE => 0,
+        opcode::BEGIN_EDIT
+        | opcode::END_EDIT
+        | opcode::IO_CHANGED
+        | opcode::UPDATE_DISPLAY
+        | opcode::WANT_MIDI
+        | opcode::NEED_IDLE
+        | opcode::PROCESS_EVENTS => 1,
+
+        // No editor is ever opened, and there are no other plugins or speakers
+        opcode::SIZE_WINDOW
+        | opcode::GET_PREVIOUS_PLUG
+        | opcode::GET_NEXT_PLUG
+        | opcode::GET_NUM_AUTOMATABLE_PARAMETERS
+        | opcode::GET_PARAMETER_QUANTIZATION
+        | opcode::GET_INPUT_SPEAKER_ARRANGEMENT
+        | opcode::GET_OUTPUT_SPEAKER_ARRANGEMENT
+        | opcode::OPEN_FILE_SELECTOR
+        | opcode::CLOSE_FILE_SELECTOR => 0,
+
+        _ => {
+            debug!("Unhandled host opcode: {opcode} (index {index}, value {value})");
+            0
+        }
+    }
+}
+
+fn write_string(text: &str, ptr: *mut c_void) -> Vst2IntPtr {
+    if ptr.is_null() {
+        return 0;
+    }
+
+    let len = text.len().min(MAX_STRING_LEN - 1);
+    unsafe {
+        std::ptr::copy_nonoverlapping(text.as_ptr(), ptr as *mut u8, len);
+        *(ptr as *mut u8).add(len) = 0;
+    }
+    1
+}
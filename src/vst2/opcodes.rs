@@ -0,0 +1,69 @@
+// Opcode numbers as defined by aeffect.h and aeffectx.h of the VST 2.4 SDK
+
+pub mod effect {
+    pub const SET_PROGRAM: i32 = 2;
+    pub const GET_PROGRAM: i32 = 3;
+    pub const GET_PROGRAM_NAME: i32 = 5;
+    pub const GET_PARAM_LABEL: i32 = 6;
+    pub const GET_PARAM_DISPLAY: i32 = 7;
+    pub const GET_PARAM_NAME: i32 = 8;
+    pub const SET_SAMPLE_RATE: i32 = 10;
+    pub const SET_BLOCK_SIZE: i32 = 11;
+    pub const CAN_BE_AUTOMATED: i32 = 26;
+    pub const GET_PROGRAM_NAME_INDEXED: i32 = 29;
+    pub const GET_VENDOR_VERSION: i32 = 49;
+    pub const CAN_DO: i32 = 51;
+    pub const GET_PARAMETER_PROPERTIES: i32 = 56;
+    pub const GET_VST_VERSION: i32 = 58;
+    pub const SHELL_GET_NEXT_PLUGIN: i32 = 70;
+}
+
+pub mod host {
+    pub const AUTOMATE: i32 = 0;
+    pub const VERSION: i32 = 1;
+    pub const CURRENT_ID: i32 = 2;
+    pub const IDLE: i32 = 3;
+    pub const WANT_MIDI: i32 = 6;
+    pub const GET_TIME: i32 = 7;
+    pub const PROCESS_EVENTS: i32 = 8;
+    pub const TEMPO_AT: i32 = 10;
+    pub const GET_NUM_AUTOMATABLE_PARAMETERS: i32 = 11;
+    pub const GET_PARAMETER_QUANTIZATION: i32 = 12;
+    pub const IO_CHANGED: i32 = 13;
+    pub const NEED_IDLE: i32 = 14;
+    pub const SIZE_WINDOW: i32 = 15;
+    pub const GET_SAMPLE_RATE: i32 = 16;
+    pub const GET_BLOCK_SIZE: i32 = 17;
+    pub const GET_INPUT_LATENCY: i32 = 18;
+    pub const GET_OUTPUT_LATENCY: i32 = 19;
+    pub const GET_PREVIOUS_PLUG: i32 = 20;
+    pub const GET_NEXT_PLUG: i32 = 21;
+    pub const WILL_REPLACE_OR_ACCUMULATE: i32 = 22;
+    pub const GET_CURRENT_PROCESS_LEVEL: i32 = 23;
+    pub const GET_AUTOMATION_STATE: i32 = 24;
+    pub const OFFLINE_START: i32 = 25;
+    pub const OFFLINE_READ: i32 = 26;
+    pub const OFFLINE_WRITE: i32 = 27;
+    pub const OFFLINE_GET_CURRENT_PASS: i32 = 28;
+    pub const OFFLINE_GET_CURRENT_META_PASS: i32 = 29;
+    pub const SET_OUTPUT_SAMPLE_RATE: i32 = 30;
+    pub const GET_OUTPUT_SPEAKER_ARRANGEMENT: i32 = 31;
+    pub const GET_VENDOR_STRING: i32 = 32;
+    pub const GET_PRODUCT_STRING: i32 = 33;
+    pub const GET_VENDOR_VERSION: i32 = 34;
+    pub const VENDOR_SPECIFIC: i32 = 35;
+    pub const SET_ICON: i32 = 36;
+    pub const CAN_DO: i32 = 37;
+    pub const GET_LANGUAGE: i32 = 38;
+    pub const OPEN_WINDOW: i32 = 39;
+    pub const CLOSE_WINDOW: i32 = 40;
+    pub const GET_DIRECTORY: i32 = 41;
+    pub const UPDATE_DISPLAY: i32 = 42;
+    pub const BEGIN_EDIT: i32 = 43;
+    pub const END_EDIT: i32 = 44;
+    pub const OPEN_FILE_SELECTOR: i32 = 45;
+    pub const CLOSE_FILE_SELECTOR: i32 = 46;
+    pub const EDIT_FILE: i32 = 47;
+    pub const GET_CHUNK_FILE: i32 = 48;
+    pub const GET_INPUT_SPEAKER_ARRANGEMENT: i32 = 49;
+}
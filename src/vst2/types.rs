@@ -7,6 +7,8 @@ use bincode::{
 use serde::{Deserialize, Serialize};
 use vst2_sys::{AEffect, HostCallbackProc};
 
+use crate::arch::FileVersionInfo;
+
 pub type Vst2IntPtr = isize;
 pub type Vst2Main = unsafe extern "C" fn(callback: HostCallbackProc) -> *mut AEffect;
 
@@ -18,6 +20,214 @@ pub struct Vst2Info {
     pub unique_id: u32,
     pub category: Vst2Category,
     pub category_raw: i32,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+    pub initial_delay: i32,
+    pub flags: Vec<Vst2Flags>,
+    pub flags_raw: i32,
+    pub capabilities: Vst2Capabilities,
+    /// ID the plugin was instantiated with when it lives inside a shell plugin
+    pub shell_id: Option<u32>,
+    /// Plugins contained in a `Vst2Category::Shell` plugin
+    pub shell_plugins: Vec<Vst2Info>,
+    pub parameters: Vec<Vst2Parameter>,
+    /// Names of the factory programs
+    pub programs: Vec<String>,
+    /// Version resource of the DLL, only set for the top-level plugin
+    pub file_version_info: Option<FileVersionInfo>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum Vst2Flags {
+    HasEditor,          // 1 << 0
+    CanReplacing,       // 1 << 4
+    ProgramChunks,      // 1 << 5
+    IsSynth,            // 1 << 8
+    NoSoundInStop,      // 1 << 9
+    CanDoubleReplacing, // 1 << 12
+}
+
+impl Vst2Flags {
+    pub fn from_raw(flags: i32) -> Vec<Vst2Flags> {
+        [
+            (1 << 0, Vst2Flags::HasEditor),
+            (1 << 4, Vst2Flags::CanReplacing),
+            (1 << 5, Vst2Flags::ProgramChunks),
+            (1 << 8, Vst2Flags::IsSynth),
+            (1 << 9, Vst2Flags::NoSoundInStop),
+            (1 << 12, Vst2Flags::CanDoubleReplacing),
+        ]
+        .into_iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, flag)| flag)
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum Vst2CanDo {
+    SendVstEvents,
+    SendVstMidiEvent,
+    ReceiveVstEvents,
+    ReceiveVstMidiEvent,
+    ReceiveVstTimeInfo,
+    Offline,
+    MidiProgramNames,
+    Bypass,
+    MidiSingleNoteTuningChange,
+    MidiKeyBasedInstrumentControl,
+}
+
+impl Vst2CanDo {
+    pub const ALL: [Vst2CanDo; 10] = [
+        Vst2CanDo::SendVstEvents,
+        Vst2CanDo::SendVstMidiEvent,
+        Vst2CanDo::ReceiveVstEvents,
+        Vst2CanDo::ReceiveVstMidiEvent,
+        Vst2CanDo::ReceiveVstTimeInfo,
+        Vst2CanDo::Offline,
+        Vst2CanDo::MidiProgramNames,
+        Vst2CanDo::Bypass,
+        Vst2CanDo::MidiSingleNoteTuningChange,
+        Vst2CanDo::MidiKeyBasedInstrumentControl,
+    ];
+
+    /// The string passed to `effCanDo`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Vst2CanDo::SendVstEvents => "sendVstEvents",
+            Vst2CanDo::SendVstMidiEvent => "sendVstMidiEvent",
+            Vst2CanDo::ReceiveVstEvents => "receiveVstEvents",
+            Vst2CanDo::ReceiveVstMidiEvent => "receiveVstMidiEvent",
+            Vst2CanDo::ReceiveVstTimeInfo => "receiveVstTimeInfo",
+            Vst2CanDo::Offline => "offline",
+            Vst2CanDo::MidiProgramNames => "midiProgramNames",
+            Vst2CanDo::Bypass => "bypass",
+            Vst2CanDo::MidiSingleNoteTuningChange => "midiSingleNoteTuningChange",
+            Vst2CanDo::MidiKeyBasedInstrumentControl => "midiKeyBasedInstrumentControl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum CanDoAnswer {
+    Yes,     //  1
+    No,      // -1
+    Unknown, //  0
+}
+
+impl CanDoAnswer {
+    pub fn from_num(num: Vst2IntPtr) -> CanDoAnswer {
+        match num {
+            1.. => CanDoAnswer::Yes,
+            ..0 => CanDoAnswer::No,
+            0 => CanDoAnswer::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct Vst2Capability {
+    pub can_do: Vst2CanDo,
+    pub answer: CanDoAnswer,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct Vst2Capabilities {
+    pub entries: Vec<Vst2Capability>,
+}
+
+impl Vst2Capabilities {
+    pub fn get(&self, can_do: Vst2CanDo) -> CanDoAnswer {
+        self.entries
+            .iter()
+            .find(|entry| entry.can_do == can_do)
+            .map_or(CanDoAnswer::Unknown, |entry| entry.answer)
+    }
+
+    pub fn supports(&self, can_do: Vst2CanDo) -> bool {
+        self.get(can_do) == CanDoAnswer::Yes
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct Vst2Parameter {
+    pub index: i32,
+    pub name: String,
+    pub label: String,
+    pub display: String,
+    /// Value right after the plugin was opened
+    pub default_value: f32,
+    pub automatable: bool,
+    /// Only present when the plugin answers `effGetParameterProperties`
+    pub properties: Option<Vst2ParameterProperties>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct Vst2ParameterProperties {
+    pub label: String,
+    pub short_label: String,
+    pub category_label: String,
+    pub flags: Vec<Vst2ParameterFlags>,
+    pub step_float: f32,
+    pub small_step_float: f32,
+    pub large_step_float: f32,
+    pub min_integer: i32,
+    pub max_integer: i32,
+    pub step_integer: i32,
+    pub large_step_integer: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub enum Vst2ParameterFlags {
+    IsSwitch,                // 1 << 0
+    UsesIntegerMinMax,       // 1 << 1
+    UsesFloatStep,           // 1 << 2
+    UsesIntStep,             // 1 << 3
+    SupportsDisplayIndex,    // 1 << 4
+    SupportsDisplayCategory, // 1 << 5
+    CanRamp,                 // 1 << 6
+}
+
+/// `VstParameterProperties` as laid out in aeffectx.h
+#[repr(C)]
+pub struct RawParameterProperties {
+    pub step_float: f32,
+    pub small_step_float: f32,
+    pub large_step_float: f32,
+    pub label: [i8; 64],
+    pub flags: i32,
+    pub min_integer: i32,
+    pub max_integer: i32,
+    pub step_integer: i32,
+    pub large_step_integer: i32,
+    pub short_label: [i8; 8],
+    pub display_index: i16,
+    pub category: i16,
+    pub num_parameters_in_category: i16,
+    pub reserved: i16,
+    pub category_label: [i8; 24],
+    pub future: [i8; 16],
+}
+
+/// `VstTimeInfo` as laid out in aeffectx.h
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawTimeInfo {
+    pub sample_pos: f64,
+    pub sample_rate: f64,
+    pub nano_seconds: f64,
+    pub ppq_pos: f64,
+    pub tempo: f64,
+    pub bar_start_pos: f64,
+    pub cycle_start_pos: f64,
+    pub cycle_end_pos: f64,
+    pub time_sig_numerator: i32,
+    pub time_sig_denominator: i32,
+    pub smpte_offset: i32,
+    pub smpte_frame_rate: i32,
+    pub samples_to_next_clock: i32,
+    pub flags: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
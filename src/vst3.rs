@@ -1,10 +1,21 @@
+use crate::bundle::resolve_vst3_binary;
+use crate::config::ScanConfig;
+use crate::error::ScanError;
+use crate::lib_loader::load_dll;
+use crate::phase::{self, ScanPhase};
+use crate::trace::{TraceDirection, TraceRecorder};
 use crate::utils::{i8_to_string, i16_to_string};
+use host::HostApplication;
 use libloading::Library;
-use std::{error::Error, mem::MaybeUninit, path::Path};
-use tracing::{debug, error, info};
+use std::{
+    ffi::c_void,
+    mem::{ManuallyDrop, MaybeUninit},
+    path::Path,
+};
+use tracing::{debug, error, info, warn};
 use types::{
     ClassFlags, ClassInfo1, ClassInfo2, ClassInfo3, ClassesInfo, FactoryFlags, FactoryInfo, IID,
-    Vst3Info, Vst3Main,
+    Vst3Info, Vst3Main, Vst3ModuleEntry,
 };
 use vst3_sys::{
     VstPtr,
@@ -13,61 +24,161 @@ use vst3_sys::{
         PFactoryInfo, kResultOk,
     },
 };
+#[cfg(windows)]
 use windows_sys::Win32::{
     Foundation::{S_FALSE, S_OK},
     System::Com::{COINIT_APARTMENTTHREADED, CoInitializeEx},
 };
 
+pub mod host;
+pub mod moduleinfo;
 pub mod types;
 
 pub struct LoadedVst3 {
+    /// Released by `drop`, while the module is still loaded
+    pub factory: ManuallyDrop<VstPtr<dyn IPluginFactory>>,
     pub lib: Library,
-    pub factory: VstPtr<dyn IPluginFactory>,
+    pub trace: Option<TraceRecorder>,
 }
 
 impl LoadedVst3 {
-    pub fn read_info(&self) -> Result<Vst3Info, Box<dyn Error>> {
-        let classes = scan_classes(self.factory.clone())?;
-        let factory_info = read_factory_info(&self.factory)?;
+    pub fn read_info(&self) -> Result<Vst3Info, ScanError> {
+        phase::enter(ScanPhase::Query);
+        let trace = self.trace.as_ref();
+        let classes = scan_classes((*self.factory).clone(), trace)?;
+        let factory_info = read_factory_info(&self.factory, trace)?;
 
         info!("CLASSES: {classes:#?}");
 
         Ok(Vst3Info {
             factory_info,
             classes,
+            compatibility: vec![],
+            file_version_info: None,
         })
     }
 }
 
-pub fn scan_vst3(path: &Path) -> Result<LoadedVst3, Box<dyn Error>> {
+impl Drop for LoadedVst3 {
+    fn drop(&mut self) {
+        // Releasing the factory calls into the module, which is unloaded when `lib` is dropped
+        unsafe { ManuallyDrop::drop(&mut self.factory) };
+
+        #[cfg(target_os = "linux")]
+        module_exit(&self.lib);
+    }
+}
+
+pub fn scan_vst3(
+    path: &Path,
+    config: &ScanConfig,
+    trace: Option<&TraceRecorder>,
+) -> Result<LoadedVst3, ScanError> {
+    #[cfg(windows)]
     unsafe {
         let hr = CoInitializeEx(std::ptr::null_mut(), COINIT_APARTMENTTHREADED as u32);
         if hr != S_OK && hr != S_FALSE {
-            return Err(format!("CoInitializeEx failed: HRESULT 0x{:X}", hr).into());
+            return Err(ScanError::ComInitFailed { result: hr });
         }
     };
 
     info!("Going to scan VST3 {}", path.display());
-    let lib = unsafe { Library::new(path) }?;
+    let binary = resolve_vst3_binary(path)?;
+    phase::enter(ScanPhase::Load);
+    let lib = load_dll(&binary, &config.search_paths)?;
+
+    phase::enter(ScanPhase::Entry);
+    #[cfg(target_os = "linux")]
+    let lib = module_entry(lib)?;
+
+    let factory = plugin_factory(&lib);
+    #[cfg(target_os = "linux")]
+    if factory.is_err() {
+        module_exit(&lib);
+    }
+    let factory = factory?;
+
+    if let Some(factory3) = factory.cast::<dyn IPluginFactory3>() {
+        // Plugins keep the context until they're unloaded and release it themselves, so it's
+        // never freed here
+        let context = Box::into_raw(HostApplication::new(&config.host, trace.cloned()));
+        let res = unsafe { factory3.set_host_context(context as *mut c_void) };
+        record(trace, "IPluginFactory3", "setHostContext", None, res);
 
-    let get_factory: libloading::Symbol<Vst3Main> = unsafe { lib.get(b"GetPluginFactory\0") }?;
+        if res != kResultOk {
+            warn!("setHostContext failed: {res}");
+        }
+    }
+
+    Ok(LoadedVst3 {
+        factory: ManuallyDrop::new(factory),
+        lib,
+        trace: trace.cloned(),
+    })
+}
+
+fn plugin_factory(lib: &Library) -> Result<VstPtr<dyn IPluginFactory>, ScanError> {
+    let get_factory: libloading::Symbol<Vst3Main> = unsafe { lib.get(b"GetPluginFactory\0") }
+        .map_err(|_| ScanError::MissingEntryPoint {
+            symbols: vec!["GetPluginFactory".to_string()],
+        })?;
     let factory_ptr = unsafe { get_factory() };
 
-    if factory_ptr.is_null() {
-        return Err("PluginFactory is null".into());
+    unsafe { VstPtr::<dyn IPluginFactory>::owned(factory_ptr as *mut _) }.ok_or_else(|| {
+        ScanError::EntryReturnedNull {
+            entry: "GetPluginFactory".to_string(),
+        }
+    })
+}
+
+// On Linux the module must be initialised with its own `dlopen` handle before
+// `GetPluginFactory` may be called.
+#[cfg(target_os = "linux")]
+fn module_entry(lib: Library) -> Result<Library, ScanError> {
+    let handle = libloading::os::unix::Library::from(lib).into_raw();
+    let lib = unsafe { libloading::os::unix::Library::from_raw(handle) };
+
+    if let Ok(entry) = unsafe { lib.get::<Vst3ModuleEntry>(b"ModuleEntry\0") }
+        && !unsafe { entry(handle) }
+    {
+        return Err(ScanError::ModuleEntryFailed);
     }
 
-    let factory = unsafe {
-        VstPtr::<dyn IPluginFactory>::owned(factory_ptr as *mut _)
-            .ok_or("Failed to cast to IPluginFactory")?
-    };
+    Ok(lib.into())
+}
 
-    // if factory.
+// Called before the module is unloaded, once everything it handed out has been released
+#[cfg(target_os = "linux")]
+fn module_exit(lib: &Library) {
+    if let Ok(exit) = unsafe { lib.get::<types::Vst3ModuleExit>(b"ModuleExit\0") }
+        && !unsafe { exit() }
+    {
+        warn!("ModuleExit failed");
+    }
+}
 
-    Ok(LoadedVst3 { lib, factory })
+fn record(
+    trace: Option<&TraceRecorder>,
+    interface: &str,
+    method: &str,
+    index: Option<i32>,
+    result: i32,
+) {
+    if let Some(trace) = trace {
+        trace.vst3_call(
+            TraceDirection::HostToPlugin,
+            interface,
+            method,
+            index,
+            result,
+        );
+    }
 }
 
-fn read_factory_info(factory: &VstPtr<dyn IPluginFactory>) -> Result<FactoryInfo, Box<dyn Error>> {
+fn read_factory_info(
+    factory: &VstPtr<dyn IPluginFactory>,
+    trace: Option<&TraceRecorder>,
+) -> Result<FactoryInfo, ScanError> {
     info!("Going to read factory info");
     let mut info = MaybeUninit::<PFactoryInfo>::uninit();
     debug!("PTR: {:?}", info.as_mut_ptr());
@@ -75,15 +186,21 @@ fn read_factory_info(factory: &VstPtr<dyn IPluginFactory>) -> Result<FactoryInfo
     let factory_ptr_2 = factory.as_ptr();
     if factory_ptr_2.is_null() {
         error!("It is null?!");
-        return Err("Plugin factory is null. Why?".into());
+        return Err(ScanError::EntryReturnedNull {
+            entry: "GetPluginFactory".to_string(),
+        });
     }
 
     info!("Going to read factory info [-1]");
     let res = unsafe { factory.get_factory_info(info.as_mut_ptr()) };
+    record(trace, "IPluginFactory", "getFactoryInfo", None, res);
 
     info!("Going to read factory info [0]");
     if res != kResultOk {
-        return Err("get_factory_info failed".into());
+        return Err(ScanError::FactoryCallFailed {
+            method: "IPluginFactory::getFactoryInfo".to_string(),
+            result: res,
+        });
     }
 
     info!("Going to read factory info [1]");
@@ -133,7 +250,7 @@ fn read_flags(mut flags: i32) -> Vec<FactoryFlags> {
     res
 }
 
-fn read_class_flags(mut flags: u32) -> Vec<ClassFlags> {
+pub(crate) fn read_class_flags(mut flags: u32) -> Vec<ClassFlags> {
     info!("Going to read class flags {flags}");
     let mut classes = vec![];
 
@@ -163,34 +280,51 @@ fn get_bit_and_shift(flags: &mut u32) -> bool {
     bit
 }
 
-fn scan_classes(factory: VstPtr<dyn IPluginFactory>) -> Result<ClassesInfo, Box<dyn Error>> {
+fn scan_classes(
+    factory: VstPtr<dyn IPluginFactory>,
+    trace: Option<&TraceRecorder>,
+) -> Result<ClassesInfo, ScanError> {
     info!("Going to scan classes");
     if let Some(factory) = factory.cast::<dyn IPluginFactory3>() {
-        let classes = scan3(factory)?;
+        let classes = scan3(factory, trace)?;
         return Ok(ClassesInfo::Classes3(classes));
     }
 
     if let Some(factory) = factory.cast::<dyn IPluginFactory2>() {
-        let classes = scan2(factory)?;
+        let classes = scan2(factory, trace)?;
         return Ok(ClassesInfo::Classes2(classes));
     }
 
-    let classes = scan1(factory)?;
+    let classes = scan1(factory, trace)?;
     Ok(ClassesInfo::Classes1(classes))
 }
 
-fn scan3(factory: VstPtr<dyn IPluginFactory3>) -> Result<Vec<ClassInfo3>, Box<dyn Error>> {
+fn scan3(
+    factory: VstPtr<dyn IPluginFactory3>,
+    trace: Option<&TraceRecorder>,
+) -> Result<Vec<ClassInfo3>, ScanError> {
     info!("Going to scan classes [3]");
     let count = unsafe { factory.count_classes() };
+    record(trace, "IPluginFactory", "countClasses", None, count);
 
     let mut classes = vec![];
 
     for i in 0..count {
         let mut info = MaybeUninit::<PClassInfoW>::uninit();
         let res = unsafe { factory.get_class_info_unicode(i, info.as_mut_ptr()) };
+        record(
+            trace,
+            "IPluginFactory3",
+            "getClassInfoUnicode",
+            Some(i),
+            res,
+        );
 
         if res != kResultOk {
-            return Err(format!("Failed to get class info for {}", i).into());
+            return Err(ScanError::FactoryCallFailed {
+                method: format!("IPluginFactory3::getClassInfoUnicode({i})"),
+                result: res,
+            });
         }
 
         let info = unsafe { info.assume_init() };
@@ -228,18 +362,26 @@ fn scan3(factory: VstPtr<dyn IPluginFactory3>) -> Result<Vec<ClassInfo3>, Box<dy
     Ok(classes)
 }
 
-fn scan2(factory: VstPtr<dyn IPluginFactory2>) -> Result<Vec<ClassInfo2>, Box<dyn Error>> {
+fn scan2(
+    factory: VstPtr<dyn IPluginFactory2>,
+    trace: Option<&TraceRecorder>,
+) -> Result<Vec<ClassInfo2>, ScanError> {
     info!("Going to scan classes [2]");
     let count = unsafe { factory.count_classes() };
+    record(trace, "IPluginFactory", "countClasses", None, count);
 
     let mut classes = vec![];
 
     for i in 0..count {
         let mut info = MaybeUninit::<PClassInfo2>::uninit();
         let res = unsafe { factory.get_class_info2(i, info.as_mut_ptr()) };
+        record(trace, "IPluginFactory2", "getClassInfo2", Some(i), res);
 
         if res != kResultOk {
-            return Err(format!("Failed to get class info for {}", i).into());
+            return Err(ScanError::FactoryCallFailed {
+                method: format!("IPluginFactory2::getClassInfo2({i})"),
+                result: res,
+            });
         }
 
         let info = unsafe { info.assume_init() };
@@ -277,18 +419,26 @@ fn scan2(factory: VstPtr<dyn IPluginFactory2>) -> Result<Vec<ClassInfo2>, Box<dy
     Ok(classes)
 }
 
-fn scan1(factory: VstPtr<dyn IPluginFactory>) -> Result<Vec<ClassInfo1>, Box<dyn Error>> {
+fn scan1(
+    factory: VstPtr<dyn IPluginFactory>,
+    trace: Option<&TraceRecorder>,
+) -> Result<Vec<ClassInfo1>, ScanError> {
     info!("Going to scan classes [1]");
     let count = unsafe { factory.count_classes() };
+    record(trace, "IPluginFactory", "countClasses", None, count);
 
     let mut classes = vec![];
 
     for i in 0..count {
         let mut info = MaybeUninit::<PClassInfo>::uninit();
         let res = unsafe { factory.get_class_info(i, info.as_mut_ptr()) };
+        record(trace, "IPluginFactory", "getClassInfo", Some(i), res);
 
         if res != kResultOk {
-            return Err(format!("Failed to get class info for {}", i).into());
+            return Err(ScanError::FactoryCallFailed {
+                method: format!("IPluginFactory::getClassInfo({i})"),
+                result: res,
+            });
         }
 
         let info = unsafe { info.assume_init() };
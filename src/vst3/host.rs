@@ -0,0 +1,71 @@
+use std::{ffi::c_void, ptr};
+
+use vst3_sys::{
+    IID, VST3,
+    base::{kInvalidArgument, kNotImplemented, kResultOk, tresult},
+    vst::{IHostApplication, String128},
+};
+
+use crate::{
+    host::HostIdentity,
+    trace::{TraceDirection, TraceRecorder},
+};
+
+/// The host context handed to `IPluginFactory3::setHostContext`
+#[VST3(implements(IHostApplication))]
+pub struct HostApplication {
+    name: Vec<u16>,
+    trace: Option<TraceRecorder>,
+}
+
+impl HostApplication {
+    pub fn new(identity: &HostIdentity, trace: Option<TraceRecorder>) -> Box<Self> {
+        Self::allocate(identity.product.encode_utf16().collect(), trace)
+    }
+
+    fn record(&self, method: &str, result: tresult) -> tresult {
+        if let Some(trace) = &self.trace {
+            trace.vst3_call(
+                TraceDirection::PluginToHost,
+                "IHostApplication",
+                method,
+                None,
+                result,
+            );
+        }
+
+        result
+    }
+}
+
+impl IHostApplication for HostApplication {
+    unsafe fn get_name(&self, name: *mut String128) -> tresult {
+        if name.is_null() {
+            return self.record("getName", kInvalidArgument);
+        }
+
+        let name = unsafe { &mut *name };
+        let len = self.name.len().min(name.len() - 1);
+
+        for (dst, &src) in name.iter_mut().zip(&self.name[..len]) {
+            *dst = src as i16;
+        }
+        name[len] = 0;
+
+        self.record("getName", kResultOk)
+    }
+
+    // Plugins only ask for messages and attribute lists here, a scanner never needs them
+    unsafe fn create_instance(
+        &self,
+        _cid: *mut IID,
+        _iid: *mut IID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if !obj.is_null() {
+            unsafe { *obj = ptr::null_mut() };
+        }
+
+        self.record("createInstance", kNotImplemented)
+    }
+}
@@ -0,0 +1,416 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::info;
+
+use super::read_class_flags;
+use super::types::{
+    ClassInfo3, ClassesInfo, Compatibility, FactoryFlags, FactoryInfo, IID, Vst3Info,
+};
+
+#[derive(Debug, Error)]
+pub enum ModuleInfoError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid moduleinfo.json: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Invalid class ID: {0}")]
+    InvalidCid(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct ModuleInfo {
+    #[serde(rename = "Factory Info")]
+    factory_info: ModuleFactoryInfo,
+    #[serde(rename = "Compatibility", default)]
+    compatibility: Vec<ModuleCompatibility>,
+    #[serde(rename = "Classes", default)]
+    classes: Vec<ModuleClass>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModuleFactoryInfo {
+    #[serde(rename = "Vendor", default)]
+    vendor: String,
+    #[serde(rename = "URL", default)]
+    url: String,
+    #[serde(rename = "E-Mail", default)]
+    email: String,
+    #[serde(rename = "Flags", default)]
+    flags: ModuleFactoryFlags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ModuleFactoryFlags {
+    #[serde(rename = "Classes Discardable", default)]
+    classes_discardable: bool,
+    #[serde(rename = "License Check", default)]
+    license_check: bool,
+    #[serde(rename = "Component Non Discardable", default)]
+    component_non_discardable: bool,
+    #[serde(rename = "Unicode", default)]
+    unicode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModuleCompatibility {
+    #[serde(rename = "New")]
+    new: String,
+    #[serde(rename = "Old", default)]
+    old: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModuleClass {
+    #[serde(rename = "CID")]
+    cid: String,
+    #[serde(rename = "Category", default)]
+    category: String,
+    #[serde(rename = "Name", default)]
+    name: String,
+    #[serde(rename = "Vendor", default)]
+    vendor: String,
+    #[serde(rename = "Version", default)]
+    version: String,
+    #[serde(rename = "SDKVersion", default)]
+    sdk_version: String,
+    #[serde(rename = "Sub Categories", default)]
+    subcategories: Vec<String>,
+    #[serde(rename = "Class Flags", default)]
+    class_flags: u32,
+    #[serde(rename = "Cardinality", default = "many_instances")]
+    cardinality: i32,
+}
+
+fn many_instances() -> i32 {
+    0x7FFF_FFFF
+}
+
+pub fn moduleinfo_path(bundle: &Path) -> PathBuf {
+    bundle
+        .join("Contents")
+        .join("Resources")
+        .join("moduleinfo.json")
+}
+
+/// Reads `Contents/Resources/moduleinfo.json` from a bundle. Returns `None` when the bundle
+/// doesn't ship one, which is the case for plugins built with SDKs older than 3.7.5.
+pub fn read_moduleinfo(bundle: &Path) -> Result<Option<Vst3Info>, ModuleInfoError> {
+    let path = moduleinfo_path(bundle);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    info!("Going to read {}", path.display());
+    let text = fs::read_to_string(path)?;
+    parse_moduleinfo(&text).map(Some)
+}
+
+pub fn parse_moduleinfo(text: &str) -> Result<Vst3Info, ModuleInfoError> {
+    let module: ModuleInfo = serde_json::from_str(&strip_json5(text))?;
+
+    let factory_flags = module.factory_info.flags;
+    let mut flags = vec![];
+
+    if factory_flags.classes_discardable {
+        flags.push(FactoryFlags::ClassesDiscardable);
+    }
+
+    if factory_flags.license_check {
+        flags.push(FactoryFlags::LicenseCheck);
+    }
+
+    if factory_flags.component_non_discardable {
+        flags.push(FactoryFlags::ComponentNonDiscardable);
+    }
+
+    if factory_flags.unicode {
+        flags.push(FactoryFlags::Unicode);
+    }
+
+    let factory_info = FactoryInfo {
+        vendor: module.factory_info.vendor,
+        url: module.factory_info.url,
+        email: module.factory_info.email,
+        flags,
+    };
+
+    let classes = module
+        .classes
+        .into_iter()
+        .map(|class| {
+            Ok(ClassInfo3 {
+                cid: parse_cid(&class.cid)?,
+                cardinality: class.cardinality,
+                category: class.category,
+                name: class.name,
+                class_flags: read_class_flags(class.class_flags),
+                subcategories: class.subcategories,
+                vendor: class.vendor,
+                version: class.version,
+                sdk_version: class.sdk_version,
+            })
+        })
+        .collect::<Result<Vec<_>, ModuleInfoError>>()?;
+
+    let compatibility = module
+        .compatibility
+        .into_iter()
+        .map(|compat| {
+            Ok(Compatibility {
+                new: parse_cid(&compat.new)?,
+                old: compat
+                    .old
+                    .iter()
+                    .map(|cid| parse_cid(cid))
+                    .collect::<Result<_, _>>()?,
+            })
+        })
+        .collect::<Result<Vec<_>, ModuleInfoError>>()?;
+
+    Ok(Vst3Info {
+        factory_info,
+        classes: ClassesInfo::Classes3(classes),
+        compatibility,
+        file_version_info: None,
+    })
+}
+
+fn parse_cid(text: &str) -> Result<IID, ModuleInfoError> {
+    IID::from_hex(text).ok_or_else(|| ModuleInfoError::InvalidCid(text.to_string()))
+}
+
+/// The SDK writes and accepts JSON5-style comments and trailing commas, which plain JSON
+/// parsers reject. Strips both while leaving string contents untouched.
+fn strip_json5(text: &str) -> String {
+    let mut without_comments = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            without_comments.push(c);
+            match c {
+                '\\' => without_comments.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                without_comments.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|&next| next != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = '\0';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                without_comments.push(' ');
+            }
+            _ => without_comments.push(c),
+        }
+    }
+
+    let mut result = String::with_capacity(without_comments.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in without_comments.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = without_comments[i + 1..].trim_start().chars().next();
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+
+        result.push(c);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As written by the SDK's moduleinfotool for the again example
+    const AGAIN: &str = r#"{
+  "Name": "AGain VST3",
+  "Version": "3.7.9.0",
+  "Factory Info": {
+    "Vendor": "Steinberg Media Technologies",
+    "URL": "http://www.steinberg.net",
+    "E-Mail": "mailto:info@steinberg.de",
+    "Flags": {
+      "Unicode": true,
+      "Classes Discardable": false,
+      "Component Non Discardable": false,
+    },
+  },
+  "Compatibility": [
+    {
+      "New": "84E8DE5F92554F5396FAE4133C935A18",
+      "Old": [
+        "B9F9ADE1CD9C4B6DA57E61E3123535FD",
+      ],
+    },
+  ],
+  "Classes": [
+    {
+      "CID": "84E8DE5F92554F5396FAE4133C935A18",
+      "Category": "Audio Module Class",
+      "Name": "AGain VST3",
+      "Vendor": "Steinberg Media Technologies",
+      "Version": "3.7.9.0",
+      "SDKVersion": "VST 3.7.9",
+      "Sub Categories": [
+        "Fx",
+      ],
+      "Class Flags": 1,
+      "Snapshots": [
+      ],
+    },
+    {
+      "CID": "D39D5B65D7AF42FA843F4AC841EB04F0",
+      "Category": "Component Controller Class",
+      "Name": "AGain VST3Controller",
+      "Vendor": "Steinberg Media Technologies",
+      "Version": "3.7.9.0",
+      "SDKVersion": "VST 3.7.9",
+      "Sub Categories": [
+      ],
+      "Class Flags": 0,
+      "Cardinality": 1,
+    },
+  ],
+}"#;
+
+    fn json(text: &str) -> serde_json::Value {
+        serde_json::from_str(&strip_json5(text)).unwrap()
+    }
+
+    #[test]
+    fn strips_comments() {
+        let text = r#"{
+            // line comment
+            "a": 1, /* block
+            comment */ "b": /* inline */ 2
+        }"#;
+
+        assert_eq!(json(text), serde_json::json!({ "a": 1, "b": 2 }));
+    }
+
+    #[test]
+    fn keeps_comment_markers_in_strings() {
+        let text = r#"{ "URL": "http://example.com/*path*/", "Note": "a // b" }"#;
+
+        assert_eq!(
+            json(text),
+            serde_json::json!({ "URL": "http://example.com/*path*/", "Note": "a // b" })
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_quotes_in_strings() {
+        let text = r#"{ "Name": "The \"Big\" // Synth,}", "Path": "C:\\", "Next": 1, }"#;
+
+        assert_eq!(
+            json(text),
+            serde_json::json!({ "Name": "The \"Big\" // Synth,}", "Path": "C:\\", "Next": 1 })
+        );
+    }
+
+    #[test]
+    fn strips_trailing_commas() {
+        let text = "{ \"a\": [1, 2, ], \"b\": { \"c\": [], }, \"d\": [ { }, ]\n,\n}";
+
+        assert_eq!(
+            json(text),
+            serde_json::json!({ "a": [1, 2], "b": { "c": [] }, "d": [{}] })
+        );
+    }
+
+    #[test]
+    fn parses_sdk_sample() {
+        let info = parse_moduleinfo(AGAIN).unwrap();
+
+        assert_eq!(info.factory_info.vendor, "Steinberg Media Technologies");
+        assert_eq!(info.factory_info.email, "mailto:info@steinberg.de");
+        assert!(matches!(
+            info.factory_info.flags[..],
+            [FactoryFlags::Unicode]
+        ));
+
+        let ClassesInfo::Classes3(classes) = &info.classes else {
+            panic!("moduleinfo.json holds the newest class info");
+        };
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes[0].name, "AGain VST3");
+        assert_eq!(classes[0].subcategories, ["Fx"]);
+        assert_eq!(classes[0].sdk_version, "VST 3.7.9");
+        assert_eq!(classes[0].cardinality, 0x7FFF_FFFF);
+        assert_eq!(classes[1].cardinality, 1);
+        assert_eq!(info.classes.audio_module_name(), Some("AGain VST3"));
+
+        assert_eq!(info.compatibility.len(), 1);
+        assert_eq!(info.compatibility[0].new.data, classes[0].cid.data);
+        assert_eq!(info.compatibility[0].old.len(), 1);
+    }
+
+    #[test]
+    fn cid_byte_order() {
+        let info = parse_moduleinfo(AGAIN).unwrap();
+        let ClassesInfo::Classes3(classes) = &info.classes else {
+            panic!("moduleinfo.json holds the newest class info");
+        };
+
+        // COM GUIDs on Windows, plain byte order elsewhere, matching what `PClassInfo` reports
+        let expected = if cfg!(windows) {
+            [
+                0x5F, 0xDE, 0xE8, 0x84, 0x55, 0x92, 0x53, 0x4F, 0x96, 0xFA, 0xE4, 0x13, 0x3C, 0x93,
+                0x5A, 0x18,
+            ]
+        } else {
+            [
+                0x84, 0xE8, 0xDE, 0x5F, 0x92, 0x55, 0x4F, 0x53, 0x96, 0xFA, 0xE4, 0x13, 0x3C, 0x93,
+                0x5A, 0x18,
+            ]
+        };
+        assert_eq!(classes[0].cid.data, expected);
+        assert_eq!(
+            info.compatibility[0].old[0].data[8..],
+            [0xA5, 0x7E, 0x61, 0xE3, 0x12, 0x35, 0x35, 0xFD]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_cids() {
+        for cid in ["84E8DE5F", "84E8DE5F92554F5396FAE4133C935AXX", ""] {
+            let text =
+                format!(r#"{{ "Factory Info": {{ }}, "Classes": [ {{ "CID": "{cid}" }} ] }}"#);
+            assert!(matches!(
+                parse_moduleinfo(&text),
+                Err(ModuleInfoError::InvalidCid(_))
+            ));
+        }
+    }
+}
@@ -3,7 +3,11 @@ use std::ffi::c_void;
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
+use crate::arch::FileVersionInfo;
+
 pub type Vst3Main = unsafe extern "system" fn() -> *mut c_void;
+pub type Vst3ModuleEntry = unsafe extern "C" fn(handle: *mut c_void) -> bool;
+pub type Vst3ModuleExit = unsafe extern "C" fn() -> bool;
 
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct IID {
@@ -11,10 +15,44 @@ pub struct IID {
     pub data: [u8; 16],
 }
 
+impl IID {
+    /// Parses the 32 hex digit form used in `moduleinfo.json`. On Windows the first three fields
+    /// are stored little-endian, the same way the plugin's own `PClassInfo` reports them.
+    pub fn from_hex(text: &str) -> Option<IID> {
+        let text = text.trim();
+        if text.len() != 32 || !text.is_ascii() {
+            return None;
+        }
+
+        let mut data = [0u8; 16];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+        }
+
+        if cfg!(windows) {
+            data[0..4].reverse();
+            data[4..6].reverse();
+            data[6..8].reverse();
+        }
+
+        Some(IID { data })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct Vst3Info {
     pub factory_info: FactoryInfo,
     pub classes: ClassesInfo,
+    pub compatibility: Vec<Compatibility>,
+    /// Version resource of the module binary
+    pub file_version_info: Option<FileVersionInfo>,
+}
+
+/// Classes that the `new` class can stand in for when a project saved with an older one is loaded
+#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct Compatibility {
+    pub new: IID,
+    pub old: Vec<IID>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
@@ -57,6 +95,32 @@ pub enum ClassesInfo {
     Classes3(Vec<ClassInfo3>),
 }
 
+impl ClassesInfo {
+    /// Name of the first audio processor class, which is what hosts list as the plugin name
+    pub fn audio_module_name(&self) -> Option<&str> {
+        let classes: Vec<(&str, &str)> = match self {
+            ClassesInfo::Classes1(classes) => classes
+                .iter()
+                .map(|class| (class.category.as_str(), class.name.as_str()))
+                .collect(),
+            ClassesInfo::Classes2(classes) => classes
+                .iter()
+                .map(|class| (class.category.as_str(), class.name.as_str()))
+                .collect(),
+            ClassesInfo::Classes3(classes) => classes
+                .iter()
+                .map(|class| (class.category.as_str(), class.name.as_str()))
+                .collect(),
+        };
+
+        classes
+            .iter()
+            .find(|(category, _)| *category == "Audio Module Class")
+            .or(classes.first())
+            .map(|(_, name)| *name)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct ClassInfo1 {
     pub cid: IID,